@@ -0,0 +1,463 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    Attribute, Data, DeriveInput, Error, Expr, Fields, GenericArgument, Lit, LitStr, Meta,
+    PathArguments, Type, parse_macro_input, spanned::Spanned,
+};
+
+/// Derives `FlexParser` and `FromMatches` for a struct (fields become `Arg`s
+/// and `Flag`s) or an enum (variants become subcommands). Doc comments become
+/// descriptions; on a type or variant, the first paragraph is the description
+/// and a longer comment also becomes its `long_about`.
+///
+/// Supported attributes:
+/// - `#[flex(name = "...")]` on the type, a field or a variant to override its name
+/// - `#[flex(short = "a")]` on a flag field to give the flag a short name
+/// - `#[flex(count)]` on an integer field to count repeated flags, as in `-vvv`
+/// - `#[flex(env = "VAR")]` on a field to fall back to an environment variable
+/// - `#[flex(default = "...")]` on a single-valued argument field, `Option<T>`
+///   included, to give it a default value,
+///   or `default = "true"` on a `bool` field for a flag negated by `--no-<name>`
+/// - `#[flex(map)]` on a field collected from `(String, String)` pairs, such as a
+///   `Vec` or `BTreeMap`, for a repeatable `--name key=value` flag
+/// - `#[flex(rest)]` on a `Vec` field to take the rest of the command line as-is
+/// - `#[flex(prompt)]` on a required argument field to ask for it as text on an
+///   interactive terminal, or `prompt = "password"` / `prompt = "confirm"`
+/// - `#[flex(subcommand)]` on a field whose type is a derived enum
+///
+/// A `Vec<T>` field becomes an argument that takes one or more values, and an
+/// `Option<Vec<T>>` field one that takes any number.
+#[proc_macro_derive(Flex, attributes(flex))]
+pub fn derive_flex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct FlexAttrs {
+    name: Option<String>,
+    short: Option<String>,
+    env: Option<String>,
+    default: Option<String>,
+    count: bool,
+    map: bool,
+    rest: bool,
+    prompt: Option<String>,
+    subcommand: bool,
+}
+
+fn flex_attrs(attrs: &[Attribute]) -> Result<FlexAttrs, Error> {
+    let mut out = FlexAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("flex")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                out.name = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("short") {
+                let short = meta.value()?.parse::<LitStr>()?;
+                if short.value().chars().count() != 1 {
+                    return Err(Error::new(short.span(), "`short` must be a single character"));
+                }
+                out.short = Some(short.value());
+            } else if meta.path.is_ident("env") {
+                out.env = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("default") {
+                out.default = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("count") {
+                out.count = true;
+            } else if meta.path.is_ident("map") {
+                out.map = true;
+            } else if meta.path.is_ident("rest") {
+                out.rest = true;
+            } else if meta.path.is_ident("prompt") {
+                out.prompt = Some(if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse::<LitStr>()?.value()
+                } else {
+                    "text".to_string()
+                });
+            } else if meta.path.is_ident("subcommand") {
+                out.subcommand = true;
+            } else {
+                return Err(meta.error("unknown flex attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(out)
+}
+
+fn doc_lines(attrs: &[Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(nv) => match &nv.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(s) => Some(s.value().trim().to_string()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+fn doc_comment(attrs: &[Attribute]) -> String {
+    doc_lines(attrs)
+        .into_iter()
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A command's doc comment split into its first paragraph, used as the
+/// description, and the whole text when there is more, used as `long_about`.
+fn command_docs(attrs: &[Attribute]) -> (String, String) {
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    for line in doc_lines(attrs).into_iter().chain([String::new()]) {
+        if !line.is_empty() {
+            current.push(line);
+        } else if !current.is_empty() {
+            paragraphs.push(current.join(" "));
+            current.clear();
+        }
+    }
+    let desc = paragraphs.first().cloned().unwrap_or_default();
+    let long_about = if paragraphs.len() > 1 {
+        paragraphs.join("\n\n")
+    } else {
+        String::new()
+    };
+    (desc, long_about)
+}
+
+fn kebab_case(ident: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in ident.chars().enumerate() {
+        if c == '_' {
+            out.push('-');
+        } else if c.is_uppercase() {
+            if i > 0 && !out.ends_with('-') {
+                out.push('-');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Returns `T` when `ty` is `wrapper<T>`.
+fn inner_type<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+fn is_bool(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.is_ident("bool"))
+}
+
+fn root() -> TokenStream2 {
+    quote!(::flex)
+}
+
+fn with_description(cmd: TokenStream2, (desc, long_about): &(String, String)) -> TokenStream2 {
+    let root = root();
+    let cmd = if desc.is_empty() {
+        cmd
+    } else {
+        quote!(<#root::Command as #root::Flex>::description(#cmd, #desc))
+    };
+    if long_about.is_empty() {
+        cmd
+    } else {
+        quote!(<#root::Command as #root::Flex>::long_about(#cmd, #long_about))
+    }
+}
+
+/// Builds the statements that add `fields` to `cmd` and the expressions that
+/// read each field back out of `matches`.
+fn expand_fields(fields: &Fields) -> Result<(Vec<TokenStream2>, Vec<TokenStream2>), Error> {
+    let root = root();
+    let mut augment = Vec::new();
+    let mut extract = Vec::new();
+
+    for field in fields {
+        let Some(ident) = &field.ident else {
+            return Err(Error::new(field.span(), "Flex fields must be named"));
+        };
+        let attrs = flex_attrs(&field.attrs)?;
+        let desc = doc_comment(&field.attrs);
+        let name = attrs.name.unwrap_or_else(|| kebab_case(&ident.to_string()));
+        let ty = &field.ty;
+        let env = attrs.env.map(|env| quote!(.env(#env)));
+
+        if attrs.subcommand {
+            if let Some(inner) = inner_type(ty, "Option") {
+                augment.push(quote! {
+                    let cmd = <#inner as #root::FlexParser>::augment(cmd);
+                });
+                extract.push(quote! {
+                    #ident: match matches.subcommand() {
+                        Some(_) => Some(<#inner as #root::FromMatches>::from_matches(matches)?),
+                        None => None,
+                    }
+                });
+            } else {
+                augment.push(quote! {
+                    let cmd = <#ty as #root::FlexParser>::augment(cmd);
+                });
+                extract.push(quote! {
+                    #ident: <#ty as #root::FromMatches>::from_matches(matches)?
+                });
+            }
+        } else if attrs.map {
+            if attrs.default.is_some() {
+                return Err(Error::new(field.span(), "`default` does not apply to map flags"));
+            }
+            let short = attrs.short.map(|short| quote!(.short(#short)));
+            augment.push(quote! {
+                let cmd = <#root::Command as #root::Flex>::flag(
+                    cmd,
+                    #root::Flag::new(#name)#short #env.description(#desc).map(),
+                );
+            });
+            extract.push(quote! {
+                #ident: matches
+                    .map(#name)
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect()
+            });
+        } else if attrs.count {
+            if attrs.default.is_some() {
+                return Err(Error::new(field.span(), "`default` does not apply to counted flags"));
+            }
+            let short = attrs.short.map(|short| quote!(.short(#short)));
+            augment.push(quote! {
+                let cmd = <#root::Command as #root::Flex>::flag(
+                    cmd,
+                    #root::Flag::new(#name)#short #env.description(#desc).counted(),
+                );
+            });
+            extract.push(quote! {
+                #ident: matches.count(#name) as #ty
+            });
+        } else if is_bool(ty) {
+            let default = match attrs.default.as_deref() {
+                None | Some("false") => None,
+                Some("true") => Some(quote!(.default_value(true))),
+                Some(_) => {
+                    return Err(Error::new(
+                        field.span(),
+                        "`default` on a flag must be \"true\" or \"false\"",
+                    ));
+                }
+            };
+            let short = attrs.short.map(|short| quote!(.short(#short)));
+            augment.push(quote! {
+                let cmd = <#root::Command as #root::Flex>::flag(
+                    cmd,
+                    #root::Flag::new(#name)#short #env.description(#desc)#default,
+                );
+            });
+            extract.push(quote! {
+                #ident: matches.is_present(#name)
+            });
+        } else {
+            if attrs.short.is_some() {
+                return Err(Error::new(field.span(), "`short` only applies to flags"));
+            }
+            let items = inner_type(ty, "Vec");
+            if attrs.rest && items.is_none() {
+                return Err(Error::new(field.span(), "`rest` only applies to `Vec` fields"));
+            }
+            let multiple = if attrs.rest { quote!(.rest()) } else { quote!(.multiple()) };
+            let option = inner_type(ty, "Option");
+            if attrs.default.is_some()
+                && (items.is_some() || option.is_some_and(|inner| inner_type(inner, "Vec").is_some()))
+            {
+                return Err(Error::new(
+                    field.span(),
+                    "`default` does not apply to arguments that take multiple values",
+                ));
+            }
+            let (required, value) = match (option, &attrs.default) {
+                (Some(inner), _) if let Some(item) = inner_type(inner, "Vec") => (
+                    Some(quote!(.multiple())),
+                    quote! {{
+                        let values = matches.all::<#item>(#name)?;
+                        (!values.is_empty()).then_some(values)
+                    }},
+                ),
+                (Some(inner), default) => (
+                    default.as_ref().map(|default| quote!(.default_value(#default))),
+                    quote!(matches.optional::<#inner>(#name)?),
+                ),
+                (None, _) if let Some(item) = items => (
+                    Some(quote!(#multiple.required())),
+                    quote!(matches.all::<#item>(#name)?),
+                ),
+                (None, Some(default)) => (
+                    Some(quote!(.default_value(#default))),
+                    quote!(matches.required::<#ty>(#name)?),
+                ),
+                (None, None) => (
+                    Some(quote!(.required())),
+                    quote!(matches.required::<#ty>(#name)?),
+                ),
+            };
+            let prompt = match attrs.prompt.as_deref() {
+                None => None,
+                Some("text") => Some(quote!(.prompt(#root::Prompt::Text))),
+                Some("password") => Some(quote!(.prompt(#root::Prompt::Password))),
+                Some("confirm") => Some(quote!(.prompt(#root::Prompt::Confirm))),
+                Some(_) => {
+                    return Err(Error::new(
+                        field.span(),
+                        "`prompt` must be \"text\", \"password\" or \"confirm\"",
+                    ));
+                }
+            };
+            augment.push(quote! {
+                let cmd = <#root::Command as #root::Flex>::arg(
+                    cmd,
+                    #root::Arg::new(#name).description(#desc)#required #env #prompt,
+                );
+            });
+            extract.push(quote!(#ident: #value));
+        }
+    }
+
+    Ok((augment, extract))
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let root = root();
+    let ident = &input.ident;
+    let attrs = flex_attrs(&input.attrs)?;
+    let name = attrs.name.unwrap_or_else(|| kebab_case(&ident.to_string()));
+    let command = with_description(
+        quote!(#root::Command::default(#name)),
+        &command_docs(&input.attrs),
+    );
+
+    let (augment, from_matches) = match &input.data {
+        Data::Struct(data) => {
+            let (augment, extract) = expand_fields(&data.fields)?;
+            (
+                quote!(#(#augment)* cmd),
+                quote!(Ok(Self { #(#extract,)* })),
+            )
+        }
+        Data::Enum(data) => {
+            let mut subcommands = Vec::new();
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                let variant_ident = &variant.ident;
+                let variant_attrs = flex_attrs(&variant.attrs)?;
+                let variant_name = variant_attrs
+                    .name
+                    .unwrap_or_else(|| kebab_case(&variant_ident.to_string()));
+                let sub = with_description(
+                    quote!(#root::Command::default(#variant_name)),
+                    &command_docs(&variant.attrs),
+                );
+                match &variant.fields {
+                    Fields::Unit => {
+                        subcommands.push(sub);
+                        arms.push(quote!(#variant_name => Ok(Self::#variant_ident)));
+                    }
+                    Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                        let ty = &fields.unnamed[0].ty;
+                        subcommands.push(quote! {
+                            <#ty as #root::FlexParser>::augment(#sub)
+                        });
+                        arms.push(quote! {
+                            #variant_name => Ok(Self::#variant_ident(
+                                <#ty as #root::FromMatches>::from_matches(matches)?
+                            ))
+                        });
+                    }
+                    Fields::Named(_) => {
+                        let (augment, extract) = expand_fields(&variant.fields)?;
+                        subcommands.push(quote!({
+                            let cmd = #sub;
+                            #(#augment)*
+                            cmd
+                        }));
+                        arms.push(quote! {
+                            #variant_name => Ok(Self::#variant_ident { #(#extract,)* })
+                        });
+                    }
+                    Fields::Unnamed(_) => {
+                        return Err(Error::new(
+                            variant.span(),
+                            "Flex tuple variants must wrap exactly one type",
+                        ));
+                    }
+                }
+            }
+            (
+                quote! {
+                    #(let cmd = <#root::Command as #root::Flex>::subcommand(cmd, #subcommands);)*
+                    cmd
+                },
+                quote! {
+                    let Some(matches) = matches.subcommand() else {
+                        return Err(#root::CommandError::MissingSubcommand(format!(
+                            "Command '{}' requires a subcommand",
+                            matches.name
+                        )));
+                    };
+                    match matches.name.as_str() {
+                        #(#arms,)*
+                        other => Err(#root::CommandError::InvalidCommand(format!(
+                            "Unknown subcommand: {}",
+                            other
+                        ))),
+                    }
+                },
+            )
+        }
+        Data::Union(_) => {
+            return Err(Error::new(input.span(), "Flex cannot be derived for unions"));
+        }
+    };
+
+    Ok(quote! {
+        impl #root::FlexParser for #ident {
+            fn command() -> #root::Command {
+                Self::augment(#command)
+            }
+
+            fn augment(cmd: #root::Command) -> #root::Command {
+                #augment
+            }
+        }
+
+        impl #root::FromMatches for #ident {
+            fn from_matches(
+                matches: &#root::Matches,
+            ) -> Result<Self, #root::CommandError> {
+                #from_matches
+            }
+        }
+    })
+}
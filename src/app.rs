@@ -0,0 +1,653 @@
+use std::{
+    cell::{OnceCell, RefCell}, env::args, error::Error, fmt::{Debug, Display}, io::{self, Write}, rc::Rc
+};
+#[cfg(feature = "spec")]
+use std::{fs, path::Path};
+
+use crate::args::command::{
+    duplicate_names, render_command_list, run_command, Command, CommandError, CommandIndex,
+};
+#[cfg(feature = "shell")]
+use crate::args::command::Flex;
+use crate::args::hooks::{Invocation, Middleware, Outcome, RunContext};
+use crate::args::state::State;
+#[cfg(feature = "async")]
+use crate::args::executor::Executor;
+use crate::output::OutputFormat;
+use crate::pager;
+use crate::plugin;
+use crate::response_file;
+#[cfg(feature = "config")]
+use crate::config::Config;
+#[cfg(feature = "shell")]
+use crate::shell;
+#[cfg(feature = "spec")]
+use crate::spec::{self, SpecFormat};
+
+#[derive(Debug, Clone)]
+pub enum AppError {
+    MissingCommand,
+    InvalidCommand(String),
+    InvalidConfiguration(String),
+    ResponseFile(String),
+    /// A plugin ran and exited with this non-zero status.
+    PluginExit(i32),
+}
+
+impl Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::MissingCommand => write!(f, "No command provided"),
+            AppError::InvalidCommand(e) => write!(f, "Unknown command: {}", e),
+            AppError::InvalidConfiguration(e) => write!(f, "Invalid configuration: {}", e),
+            AppError::ResponseFile(e) => write!(f, "Response file: {}", e),
+            AppError::PluginExit(code) => write!(f, "Plugin exited with status {}", code),
+        }
+    }
+}
+
+impl Error for AppError {}
+
+/// A closure registered on the [`App`] under a name, so a spec file can bind it
+/// to the commands it declares.
+pub type NamedAction = Rc<dyn Fn(&[String]) -> Outcome + 'static>;
+
+#[derive(Default)]
+pub struct App {
+    pub name: String,
+    pub about: String,
+    pub commands: Vec<Command>,
+    /// Command run when the command line names none, instead of the help.
+    pub default_command: Option<String>,
+    pub actions: Vec<(String, NamedAction)>,
+    /// Wraps every command run, outermost first.
+    pub middleware: Vec<Middleware>,
+    /// Runs async actions; [`CurrentThread`](crate::CurrentThread) when unset.
+    #[cfg(feature = "async")]
+    pub executor: Option<Rc<dyn Executor>>,
+    /// Shared with actions added through [`Flex::state_action`](crate::Flex::state_action).
+    pub state: State,
+    pub env_prefix: String,
+    /// Maximum nesting of `@file` arguments, `None` when expansion is off.
+    pub response_files: Option<usize>,
+    /// Whether long output goes through the pager unless a command says otherwise.
+    pub pager: bool,
+    /// Whether unknown commands run `<name>-<command>` programs from `PATH`.
+    pub plugins: bool,
+    #[cfg(feature = "config")]
+    pub config: Config,
+    /// Whether the built-in `shell` command is enabled.
+    #[cfg(feature = "shell")]
+    pub shell: bool,
+    /// Where the shell keeps its line history, `None` for no history file.
+    #[cfg(feature = "shell")]
+    pub history_file: Option<std::path::PathBuf>,
+    index: OnceCell<CommandIndex>,
+}
+
+impl Debug for App {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("App")
+            .field("name", &self.name)
+            .field("about", &self.about)
+            .field("commands", &self.commands)
+            .field("env_prefix", &self.env_prefix)
+            .field("response_files", &self.response_files)
+            .field("pager", &self.pager)
+            .field("plugins", &self.plugins)
+            .field(
+                "actions",
+                &self.actions.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            )
+            .field("middleware", &self.middleware.len())
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl App {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn about(mut self, about: impl Into<String>) -> Self {
+        self.about = about.into();
+        self
+    }
+
+    /// Runs command `name` for a bare invocation, or one with only flags, which
+    /// are passed on to it. `help`, `-h` and `--help` still show the help.
+    pub fn default_command(mut self, name: impl Into<String>) -> Self {
+        self.default_command = Some(name.into());
+        self
+    }
+
+    pub fn add_commands(mut self, commands: Vec<Command>) -> Self {
+        self.commands = Vec::new();
+        for cmd in commands {
+            self = self.add_command(cmd);
+        }
+        self.index.take();
+        self
+    }
+
+    pub fn add_command(mut self, mut cmd: Command) -> Self {
+        if !self.env_prefix.is_empty() {
+            cmd.apply_env_prefix(&self.env_prefix);
+        }
+        // Arrays given to single values are left out here and reported by `validate`.
+        #[cfg(feature = "config")]
+        self.config.apply(&mut cmd, &mut Vec::new());
+        self.commands.push(cmd);
+        self.index.take();
+        self
+    }
+
+    /// Adds a config file layer; later files override earlier ones, the
+    /// environment and the command line override both. A missing file is skipped.
+    #[cfg(feature = "config")]
+    pub fn config_file(mut self, path: impl AsRef<std::path::Path>) -> Result<Self, AppError> {
+        self.config = self.config.load(path)?;
+        let mut problems = Vec::new();
+        for cmd in self.commands.iter_mut() {
+            problems.extend(self.config.apply(cmd, &mut Vec::new()));
+        }
+        if problems.is_empty() {
+            Ok(self)
+        } else {
+            Err(AppError::InvalidConfiguration(problems.join("\n")))
+        }
+    }
+
+    /// Loads the user config file and then the project-local one, see
+    /// [`Config::default_paths`].
+    #[cfg(feature = "config")]
+    pub fn default_config_files(mut self) -> Result<Self, AppError> {
+        for path in Config::default_paths(&self.name) {
+            self = self.config_file(path)?;
+        }
+        Ok(self)
+    }
+
+    /// Expands `@file` arguments into the arguments listed in that file before
+    /// dispatch. `@file`s inside a file are followed up to `max_depth` levels
+    /// deep, so 0 expands only the files named on the command line. `@@text`
+    /// passes `@text` through, and nothing after a `--` is expanded.
+    ///
+    /// Inside a file, arguments are split on whitespace with shell-like quoting
+    /// (`'...'`, `"..."` and `\` escapes), and lines whose first non-blank
+    /// character is `#` are comments. Nested paths are relative to the file that
+    /// names them.
+    pub fn response_files(mut self, max_depth: usize) -> Self {
+        self.response_files = Some(max_depth);
+        self
+    }
+
+    /// Runs `flex foo` as the `flex-foo` program found on `PATH` when no
+    /// command is named `foo`. Named first, the plugin gets the rest of the
+    /// command line exactly as given, app flags and `@file`s included. Plugins
+    /// are listed in the help, and a failing plugin's exit status comes back as
+    /// [`AppError::PluginExit`].
+    pub fn plugins(mut self) -> Self {
+        self.plugins = true;
+        self
+    }
+
+    /// Shows output taller than the terminal through `$PAGER` (`less -R` when
+    /// unset). Only applies on a terminal, and `--no-pager` before the command
+    /// name or an empty `<PREFIX>_PAGER` variable turns it off for one run.
+    pub fn pager(mut self, enabled: bool) -> Self {
+        self.pager = enabled;
+        self
+    }
+
+    /// Derives an environment variable for every argument and flag that has none,
+    /// e.g. `FLEX_ALL` for `--all` with prefix `FLEX`. Applies to commands added
+    /// before and after this call.
+    pub fn env_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.env_prefix = prefix.into();
+        for cmd in self.commands.iter_mut() {
+            cmd.apply_env_prefix(&self.env_prefix);
+        }
+        self
+    }
+
+    /// Looks up a top-level command by name or alias.
+    pub fn find_command(&self, name: &str) -> Option<&Command> {
+        let index = self
+            .index
+            .get_or_init(|| CommandIndex::new(&self.commands, &[]));
+        index.find_command(&self.commands, name)
+    }
+
+    /// Freezes the command tree: builds every lookup index up front instead of
+    /// lazily along the dispatch path.
+    pub fn finalize(self) -> Self {
+        self.find_command("");
+        for cmd in &self.commands {
+            cmd.finalize();
+        }
+        self
+    }
+
+    /// Registers `action` under `name` so spec files can refer to it with
+    /// `action = "<name>"`.
+    pub fn register_action<F, E>(mut self, name: impl Into<String>, action: F) -> Self
+    where
+        F: Fn(&[String]) -> Result<String, E> + 'static,
+        E: Error + 'static,
+    {
+        let action: NamedAction = Rc::new(move |args: &[String]| {
+            action(args)
+                .map(Into::into)
+                .map_err(|e| Box::new(e) as Box<dyn Error>)
+        });
+        self.actions.push((name.into(), action));
+        self
+    }
+
+    /// Wraps every command run through the app with `middleware`, which gets
+    /// the invocation and a `next` function that runs the command. Middleware
+    /// added first runs outermost.
+    pub fn middleware<F>(mut self, middleware: F) -> Self
+    where
+        F: Fn(&Invocation, &dyn Fn() -> Outcome) -> Outcome + 'static,
+    {
+        self.middleware.push(Rc::new(middleware));
+        self
+    }
+
+    /// Runs async actions with `executor`, e.g. a closure around a runtime's
+    /// `block_on`.
+    #[cfg(feature = "async")]
+    pub fn executor(mut self, executor: impl Executor + 'static) -> Self {
+        self.executor = Some(Rc::new(executor));
+        self
+    }
+
+    /// Shares `value` with every action that takes the app's [`State`]. Build
+    /// expensive handles once here instead of in each action.
+    pub fn state<T: 'static>(mut self, value: T) -> Self {
+        self.state.insert(value);
+        self
+    }
+
+    pub fn get_action(&self, name: &str) -> Option<NamedAction> {
+        self.actions
+            .iter()
+            .find(|(action_name, _)| action_name == name)
+            .map(|(_, action)| action.clone())
+    }
+
+    /// Adds the commands declared in a TOML or JSON spec. Register any actions the
+    /// spec names before calling this.
+    #[cfg(feature = "spec")]
+    pub fn spec(self, source: &str, format: SpecFormat) -> Result<Self, AppError> {
+        let value = spec::parse(source, format)?;
+        spec::apply(self, &value)
+    }
+
+    /// Like [`App::spec`], picking the format from the file extension.
+    #[cfg(feature = "spec")]
+    pub fn spec_file(self, path: impl AsRef<Path>) -> Result<Self, AppError> {
+        let path = path.as_ref();
+        let format = SpecFormat::from_path(path).ok_or_else(|| {
+            AppError::InvalidConfiguration(format!(
+                "spec file '{}' must end in .toml or .json",
+                path.display()
+            ))
+        })?;
+        let source = fs::read_to_string(path).map_err(|e| {
+            AppError::InvalidConfiguration(format!(
+                "cannot read spec file '{}': {}",
+                path.display(),
+                e
+            ))
+        })?;
+        self.spec(&source, format)
+    }
+
+    pub fn help(self) -> Self {
+        self.add_command(Command::help_command())
+    }
+
+    /// Adds a `shell` command that reads command lines interactively, with tab
+    /// completion and a history file (see [`App::history_file`]). `exit` leaves it.
+    #[cfg(feature = "shell")]
+    pub fn shell(mut self) -> Self {
+        self.shell = true;
+        if self.history_file.is_none() {
+            self.history_file = shell::default_history_file(&self.name);
+        }
+        self.add_command(Command::default("shell").description("Start an interactive shell"))
+    }
+
+    /// Keeps the shell history in `path` instead of
+    /// `$XDG_STATE_HOME/<app>/history`.
+    #[cfg(feature = "shell")]
+    pub fn history_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.history_file = Some(path.into());
+        self
+    }
+
+    /// Renders the help text shown by the app's `help` command.
+    pub fn render_help(&self) -> String {
+        let mut output = format!("Usage: {} <command> [<args>]\n", self.name);
+        if !self.about.is_empty() {
+            output.push_str(&format!("{}\n\n", self.about));
+        }
+        render_command_list(&mut output, "Commands", &self.commands);
+        let plugins = if self.plugins {
+            plugin::discover(&self.name)
+        } else {
+            Vec::new()
+        };
+        if !plugins.is_empty() {
+            output.push_str("\nPlugins:\n");
+            for name in plugins {
+                output.push_str(&format!("  {}\n", name));
+            }
+        }
+        output.trim().to_string()
+    }
+
+    /// Runs `cmd`, or renders the app help when `cmd` is the built-in `help`.
+    fn dispatch(&self, cmd: &Command, args: &[String], ctx: &RunContext) -> Outcome {
+        #[cfg(feature = "shell")]
+        if self.shell && cmd.get_cmd_name() == "shell" {
+            return shell::run(self).map(Into::into);
+        }
+        if cmd.is_help() {
+            Ok(self.render_help().into())
+        } else {
+            run_command(cmd, args, ctx)
+        }
+    }
+
+    /// Checks the whole command tree for duplicate command or flag names,
+    /// clashing short flags and required arguments declared after optional ones.
+    pub fn validate(&self) -> Result<(), AppError> {
+        let mut problems = duplicate_names(&self.name, &self.commands);
+        for cmd in &self.commands {
+            problems.extend(cmd.validate(&format!("{} {}", self.name, cmd.get_cmd_name())));
+        }
+        #[cfg(feature = "config")]
+        for cmd in &self.commands {
+            problems.extend(self.config.apply(&mut cmd.clone(), &mut Vec::new()));
+        }
+        if let Some(name) = &self.default_command
+            && self.find_command(name).is_none()
+        {
+            problems.push(format!("{}: default command '{}' does not exist", self.name, name));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::InvalidConfiguration(problems.join("\n")))
+        }
+    }
+
+    pub fn run(&self) -> Result<String, Box<dyn Error>> {
+        if cfg!(debug_assertions) {
+            self.validate()?;
+        }
+        self.run_from(args().skip(1).collect())
+    }
+
+    /// Runs the command line `args`, given without the program name, and
+    /// renders the result in the format picked with `--output`. That flag goes
+    /// before the command name, or after it when the command parses its
+    /// arguments, has no rest argument and has no `output` flag of its own. Streaming actions write
+    /// straight to standard output, and output shown through the pager is not
+    /// returned.
+    pub fn run_from(&self, args: Vec<String>) -> Result<String, Box<dyn Error>> {
+        self.run_to(args, &mut io::stdout(), true)
+    }
+
+    /// Like [`App::run_from`], with streaming actions writing to `out` and no
+    /// pager.
+    pub fn run_with_output(&self, args: Vec<String>, out: &mut dyn Write) -> Result<String, Box<dyn Error>> {
+        self.run_to(args, out, false)
+    }
+
+    fn run_to(&self, mut args: Vec<String>, out: &mut dyn Write, page: bool) -> Result<String, Box<dyn Error>> {
+        let out = RefCell::new(out);
+        let mut ctx = RunContext::new(&self.name, &self.middleware, &self.state).with_writer(&out);
+        #[cfg(feature = "async")]
+        if let Some(executor) = &self.executor {
+            ctx = ctx.with_executor(executor.as_ref());
+        }
+        // A plugin named first gets the rest of the command line untouched.
+        if let Some((name, plugin_args)) = args.split_first()
+            && let Some(outcome) = self.run_plugin(name, plugin_args, &ctx)
+        {
+            return Ok(outcome?.render(OutputFormat::Text));
+        }
+        if let Some(max_depth) = self.response_files {
+            args = response_file::expand(args, max_depth)?;
+        }
+        // App flags go before the command name; after it they are the command's.
+        let mut format = None;
+        let mut page = page;
+        #[cfg(feature = "config")]
+        let mut show_sources = false;
+        loop {
+            match args.first().map(String::as_str) {
+                Some("--no-pager") => {
+                    args.remove(0);
+                    page = false;
+                }
+                Some("--no-input") => {
+                    args.remove(0);
+                    ctx = ctx.without_input();
+                }
+                #[cfg(feature = "config")]
+                Some("--show-config-sources") => {
+                    args.remove(0);
+                    show_sources = true;
+                }
+                Some(arg) if is_output_flag(arg) => format = Some(take_output_format(&mut args, 0)?),
+                _ => break,
+            }
+        }
+        #[cfg(feature = "config")]
+        if show_sources {
+            return self.show_config_sources(&args);
+        }
+        if format.is_none()
+            && self.leaves_output_flag(&args)
+            && let Some(i) = args.iter().take_while(|arg| *arg != "--").position(|arg| is_output_flag(arg))
+        {
+            format = Some(take_output_format(&mut args, i)?);
+        }
+        let format = format.unwrap_or(OutputFormat::Text);
+        let page = page && self.pager_for(&args);
+        let output = self.run_args(args, &ctx)?.render(format);
+        if page && pager::page(&output, &self.pager_var()) {
+            return Ok(String::new());
+        }
+        Ok(output)
+    }
+
+    /// Whether `args` go to the default command: they are empty or start
+    /// with a flag other than help.
+    fn takes_default_command(args: &[String]) -> bool {
+        args.first()
+            .is_none_or(|arg| arg.starts_with('-') && arg != "-h" && arg != "--help")
+    }
+
+    /// The commands `args` run, outermost first, following subcommand names
+    /// and default commands the way dispatch does.
+    fn resolve(&self, args: &[String]) -> Vec<&Command> {
+        let (first, mut rest) = match args.first().and_then(|name| self.find_command(name)) {
+            Some(cmd) => (cmd, &args[1..]),
+            None if Self::takes_default_command(args) => {
+                match self.default_command.as_deref().and_then(|name| self.find_command(name)) {
+                    Some(cmd) => (cmd, args),
+                    None => return Vec::new(),
+                }
+            }
+            None => return Vec::new(),
+        };
+        let mut path = vec![first];
+        while let Some(Command::Flex(cmd)) = path.last() {
+            let next = match rest.first().and_then(|name| cmd.find_subcommand(name)) {
+                Some(sub_cmd) => {
+                    rest = &rest[1..];
+                    sub_cmd
+                }
+                None => match cmd.default_subcommand_for(rest) {
+                    Some(sub_cmd) => sub_cmd,
+                    None => break,
+                },
+            };
+            path.push(next);
+        }
+        path
+    }
+
+    /// Whether an `--output` after the command name is the app's rather than
+    /// the command's: the command `args` run parses its arguments, has no rest
+    /// argument to pass words through, and declares no `output` flag of its own.
+    fn leaves_output_flag(&self, args: &[String]) -> bool {
+        match self.resolve(args).last() {
+            Some(Command::Flex(cmd)) => {
+                cmd.action.as_ref().is_some_and(|action| !action.is_raw())
+                    && !cmd.args.iter().any(|arg| arg.rest)
+                    && cmd.find_flag("output").is_none()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the command `args` name pages its output: the setting of the
+    /// innermost command that has one, or the app's.
+    fn pager_for(&self, args: &[String]) -> bool {
+        self.resolve(args)
+            .iter()
+            .rev()
+            .find_map(|cmd| match cmd {
+                Command::Flex(cmd) => cmd.pager,
+                _ => None,
+            })
+            .unwrap_or(self.pager)
+    }
+
+    /// The variable that overrides `$PAGER`, e.g. `FLEX_PAGER`.
+    fn pager_var(&self) -> String {
+        let prefix = if self.env_prefix.is_empty() {
+            self.name.to_uppercase().replace('-', "_")
+        } else {
+            self.env_prefix.clone()
+        };
+        format!("{}_PAGER", prefix)
+    }
+
+    fn run_args(&self, args: Vec<String>, ctx: &RunContext) -> Outcome {
+        if let Some(name) = &self.default_command
+            && Self::takes_default_command(&args)
+        {
+            let cmd = self.find_command(name).ok_or_else(|| {
+                AppError::InvalidConfiguration(format!("default command '{}' does not exist", name))
+            })?;
+            return self.dispatch(cmd, &args, ctx);
+        }
+        if args.is_empty()
+            || args
+                .first()
+                .is_some_and(|arg| arg == "help" || arg == "--help" || arg == "-h")
+        {
+            if let Some(help) = self.find_command("help") {
+                return self.dispatch(help, &[], ctx);
+            }
+            return Err(Box::new(AppError::InvalidConfiguration(
+                "No help command defined. Add with `.help()`".to_string(),
+            )));
+        }
+
+        let command_name = &args[0];
+        let command_args = &args[1..];
+
+        if let Some(cmd) = self.find_command(command_name) {
+            return self.dispatch(cmd, command_args, ctx);
+        }
+        if let Some(outcome) = self.run_plugin(command_name, command_args, ctx) {
+            return outcome;
+        }
+
+        ctx.fail(
+            &args,
+            AppError::InvalidCommand(format!("Unknown command: {}", command_name)),
+        )
+    }
+
+    /// Runs the `<app>-<name>` plugin with `args` when plugins are on and no
+    /// command is named `name`.
+    fn run_plugin(&self, name: &str, args: &[String], ctx: &RunContext) -> Option<Outcome> {
+        if !self.plugins || self.find_command(name).is_some() {
+            return None;
+        }
+        let path = plugin::find(&self.name, name)?;
+        Some(ctx.enter(name, None).run(args, None, &|| plugin::run(&path, args)))
+    }
+
+    /// Parses `args` without running anything and reports where each value came
+    /// from, for `--show-config-sources`.
+    #[cfg(feature = "config")]
+    fn show_config_sources(&self, args: &[String]) -> Result<String, Box<dyn Error>> {
+        let mut output = String::from("Config files:\n");
+        for file in &self.config.files {
+            output.push_str(&format!("  {}\n", file.path.display()));
+        }
+        let Some((command_name, command_args)) = args.split_first() else {
+            return Err(Box::new(AppError::MissingCommand));
+        };
+        let cmd = self.find_command(command_name).ok_or_else(|| {
+            AppError::InvalidCommand(format!("Unknown command: {}", command_name))
+        })?;
+
+        let mut matches = Some(cmd.parse_with(command_args, false)?);
+        let mut path = self.name.clone();
+        while let Some(current) = matches {
+            path = format!("{} {}", path, current.name);
+            output.push_str(&format!("\n{}\n", path));
+            for (name, source) in &current.sources {
+                let values = current.values_of(name);
+                let shown = if !values.is_empty() {
+                    format!("<{}> = {}", name, values.join(" "))
+                } else {
+                    match current.count(name) {
+                        0 => format!("--no-{}", name),
+                        1 => format!("--{}", name),
+                        count => format!("--{} x{}", name, count),
+                    }
+                };
+                output.push_str(&format!("  {:<24} {}\n", shown, source));
+            }
+            matches = current.subcommand.map(|sub| *sub);
+        }
+        Ok(output.trim().to_string())
+    }
+}
+
+fn is_output_flag(arg: &str) -> bool {
+    arg == "--output" || arg.starts_with("--output=")
+}
+
+/// Removes the `--output <format>` or `--output=<format>` at `i` from `args`,
+/// returning the format it names.
+fn take_output_format(args: &mut Vec<String>, i: usize) -> Result<OutputFormat, CommandError> {
+    let name = match args.remove(i).strip_prefix("--output=") {
+        Some(name) => name.to_string(),
+        None if i < args.len() => args.remove(i),
+        None => {
+            return Err(CommandError::InvalidArgument(
+                "--output requires a format: text, json, yaml or csv".to_string(),
+            ));
+        }
+    };
+    name.parse().map_err(CommandError::InvalidArgument)
+}
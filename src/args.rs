@@ -0,0 +1,10 @@
+pub mod arg;
+pub mod command;
+#[cfg(feature = "async")]
+pub mod executor;
+pub mod flag;
+pub mod hooks;
+pub mod matches;
+pub mod parser;
+pub mod prompt;
+pub mod state;
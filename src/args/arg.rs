@@ -0,0 +1,233 @@
+use std::path::PathBuf;
+
+use super::prompt::Prompt;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Arg {
+    pub name: String,
+    pub desc: String,
+    pub required: bool,
+    pub env: String,
+    pub default: Option<String>,
+    /// Values and file found by the config layer, if any.
+    pub config: Option<(Vec<String>, PathBuf)>,
+    pub multiple: bool,
+    pub min: usize,
+    pub max: Option<usize>,
+    pub rest: bool,
+    pub prompt: Option<Prompt>,
+}
+
+impl Arg {
+    pub fn new(name: impl Into<String>) -> Self {
+        Arg {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn flex(flag: Arg) -> Self {
+        Self {
+            name: flag.name,
+            desc: flag.desc,
+            required: flag.required,
+            env: flag.env,
+            default: flag.default,
+            config: flag.config,
+            multiple: flag.multiple,
+            min: flag.min,
+            max: flag.max,
+            rest: flag.rest,
+            prompt: flag.prompt,
+        }
+    }
+
+    pub fn description(mut self, content: impl Into<String>) -> Self {
+        self.desc = content.into();
+        Self {
+            name: self.name,
+            desc: self.desc,
+            required: self.required,
+            env: self.env,
+            default: self.default,
+            config: self.config,
+            multiple: self.multiple,
+            min: self.min,
+            max: self.max,
+            rest: self.rest,
+            prompt: self.prompt,
+        }
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        Self {
+            name: self.name,
+            desc: self.desc,
+            required: self.required,
+            env: self.env,
+            default: self.default,
+            config: self.config,
+            multiple: self.multiple,
+            min: self.min,
+            max: self.max,
+            rest: self.rest,
+            prompt: self.prompt,
+        }
+    }
+
+    /// Reads the value from environment variable `var` when it is not given on
+    /// the command line. For an argument taking multiple values the variable is
+    /// split into words like a shell would, so `"a b"` gives two values and
+    /// `"'a b'"` one.
+    pub fn env(mut self, var: impl Into<String>) -> Self {
+        self.env = var.into();
+        Self {
+            name: self.name,
+            desc: self.desc,
+            required: self.required,
+            env: self.env,
+            default: self.default,
+            config: self.config,
+            multiple: self.multiple,
+            min: self.min,
+            max: self.max,
+            rest: self.rest,
+            prompt: self.prompt,
+        }
+    }
+
+    /// Value used when the argument is given neither on the command line nor
+    /// through its environment variable.
+    pub fn default_value(mut self, value: impl Into<String>) -> Self {
+        self.default = Some(value.into());
+        Self {
+            name: self.name,
+            desc: self.desc,
+            required: self.required,
+            env: self.env,
+            default: self.default,
+            config: self.config,
+            multiple: self.multiple,
+            min: self.min,
+            max: self.max,
+            rest: self.rest,
+            prompt: self.prompt,
+        }
+    }
+
+    /// Takes every remaining positional value instead of one. Only the last
+    /// argument of a command may take multiple values.
+    pub fn multiple(mut self) -> Self {
+        self.multiple = true;
+        Self {
+            name: self.name,
+            desc: self.desc,
+            required: self.required,
+            env: self.env,
+            default: self.default,
+            config: self.config,
+            multiple: self.multiple,
+            min: self.min,
+            max: self.max,
+            rest: self.rest,
+            prompt: self.prompt,
+        }
+    }
+
+    /// Requires at least `count` values, which makes the argument take
+    /// multiple values and, for a non-zero `count`, required.
+    pub fn min_values(mut self, count: usize) -> Self {
+        self.multiple = true;
+        self.min = count;
+        self.required = self.required || count > 0;
+        Self {
+            name: self.name,
+            desc: self.desc,
+            required: self.required,
+            env: self.env,
+            default: self.default,
+            config: self.config,
+            multiple: self.multiple,
+            min: self.min,
+            max: self.max,
+            rest: self.rest,
+            prompt: self.prompt,
+        }
+    }
+
+    /// Accepts at most `count` values, which makes the argument take multiple
+    /// values.
+    pub fn max_values(mut self, count: usize) -> Self {
+        self.multiple = true;
+        self.max = Some(count);
+        Self {
+            name: self.name,
+            desc: self.desc,
+            required: self.required,
+            env: self.env,
+            default: self.default,
+            config: self.config,
+            multiple: self.multiple,
+            min: self.min,
+            max: self.max,
+            rest: self.rest,
+            prompt: self.prompt,
+        }
+    }
+
+    /// Takes the rest of the command line as-is once its first value is seen,
+    /// so later `-x` words are values rather than flags.
+    pub fn rest(mut self) -> Self {
+        self.multiple = true;
+        self.rest = true;
+        Self {
+            name: self.name,
+            desc: self.desc,
+            required: self.required,
+            env: self.env,
+            default: self.default,
+            config: self.config,
+            multiple: self.multiple,
+            min: self.min,
+            max: self.max,
+            rest: self.rest,
+            prompt: self.prompt,
+        }
+    }
+
+    /// Asks for the value on an interactive terminal when a required argument
+    /// is given nowhere else, instead of failing.
+    pub fn prompt(mut self, prompt: Prompt) -> Self {
+        self.prompt = Some(prompt);
+        Self {
+            name: self.name,
+            desc: self.desc,
+            required: self.required,
+            env: self.env,
+            default: self.default,
+            config: self.config,
+            multiple: self.multiple,
+            min: self.min,
+            max: self.max,
+            rest: self.rest,
+            prompt: self.prompt,
+        }
+    }
+
+    /// Fewest values the command line must give, counting a required argument
+    /// as at least one.
+    pub fn min_count(&self) -> usize {
+        if self.required { self.min.max(1) } else { 0 }
+    }
+
+    /// How the argument appears in a usage line, e.g. `<path>...` or `[<path>]`.
+    pub fn usage(&self) -> String {
+        let dots = if self.multiple { "..." } else { "" };
+        if self.required {
+            format!("<{}>{}", self.name, dots)
+        } else {
+            format!("[<{}>{}]", self.name, dots)
+        }
+    }
+}
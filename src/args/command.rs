@@ -0,0 +1,1769 @@
+use std::{
+    cell::OnceCell,
+    collections::HashMap,
+    error::Error,
+    fmt::{Debug, Display},
+    io::Write,
+    rc::Rc,
+};
+
+use crate::output::{Output, OutputFormat};
+use crate::response_file::split_words;
+
+use super::{
+    arg::Arg,
+    flag::Flag,
+    hooks::{Hooks, Invocation, Outcome, RunContext, Streamed},
+    matches::{FromMatches, Matches, ValueSource},
+    prompt::Prompt,
+    state::State,
+};
+#[cfg(feature = "async")]
+use super::executor::BoxFuture;
+#[cfg(feature = "async")]
+use std::future::Future;
+
+type ArgsAction = Box<dyn Fn(&[String]) -> Outcome + 'static>;
+type MatchesAction = Box<dyn Fn(&Matches) -> Outcome + 'static>;
+type StateAction = Box<dyn Fn(&State, &Matches) -> Outcome + 'static>;
+type StreamAction = Box<dyn Fn(&Matches, &mut dyn Write) -> Streamed + 'static>;
+#[cfg(feature = "async")]
+type AsyncArgsAction = Box<dyn Fn(&[String]) -> BoxFuture + 'static>;
+#[cfg(feature = "async")]
+type AsyncMatchesAction = Box<dyn Fn(&Matches) -> BoxFuture + 'static>;
+
+/// What a command runs once dispatch reaches it: either the raw arguments,
+/// exactly as typed and including any `--`, or the [`Matches`] parsed against
+/// the command's declared args and flags, optionally with the app's [`State`].
+/// The `Async` variants return a future that the app's executor runs.
+/// `Stream` writes its output as it goes instead of returning it.
+/// `Help` renders the help of the command it is a subcommand of.
+pub enum Action {
+    Args(ArgsAction),
+    Matches(MatchesAction),
+    State(StateAction),
+    Stream(StreamAction),
+    #[cfg(feature = "async")]
+    AsyncArgs(AsyncArgsAction),
+    #[cfg(feature = "async")]
+    AsyncMatches(AsyncMatchesAction),
+    Help,
+}
+
+impl Action {
+    /// Whether the action takes the raw arguments rather than parsed ones.
+    pub(crate) fn is_raw(&self) -> bool {
+        match self {
+            Action::Args(_) => true,
+            #[cfg(feature = "async")]
+            Action::AsyncArgs(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Name lookups for one command's subcommands (names and aliases) and flags.
+/// Built on first lookup, or up front by [`Command::finalize`], so dispatch
+/// cost does not grow with the number of siblings. The index remembers how many
+/// subcommands and flags it covered: once the public vectors are edited to a
+/// different length it is stale and lookups search linearly instead, and a hit
+/// is always checked against the entry it points at.
+#[derive(Debug, Default)]
+pub struct CommandIndex {
+    commands: HashMap<String, usize>,
+    long_flags: HashMap<String, usize>,
+    short_flags: HashMap<String, usize>,
+    commands_len: usize,
+    flags_len: usize,
+}
+
+impl CommandIndex {
+    pub fn new(commands: &[Command], flags: &[Flag]) -> Self {
+        let mut index = CommandIndex {
+            commands_len: commands.len(),
+            flags_len: flags.len(),
+            ..Default::default()
+        };
+        for (i, cmd) in commands.iter().enumerate() {
+            index.commands.entry(cmd.get_cmd_name().to_string()).or_insert(i);
+        }
+        for (i, cmd) in commands.iter().enumerate() {
+            for alias in cmd.get_aliases() {
+                index.commands.entry(alias.clone()).or_insert(i);
+            }
+        }
+        for (i, flag) in flags.iter().enumerate() {
+            index.long_flags.entry(flag.name.clone()).or_insert(i);
+            if !flag.short.is_empty() {
+                index.short_flags.entry(flag.short.clone()).or_insert(i);
+            }
+        }
+        index
+    }
+
+    pub fn command(&self, name: &str) -> Option<usize> {
+        self.commands.get(name).copied()
+    }
+
+    pub fn long_flag(&self, name: &str) -> Option<usize> {
+        self.long_flags.get(name).copied()
+    }
+
+    pub fn short_flag(&self, short: &str) -> Option<usize> {
+        self.short_flags.get(short).copied()
+    }
+
+    /// The command in `commands` named or aliased `name`.
+    pub fn find_command<'a>(&self, commands: &'a [Command], name: &str) -> Option<&'a Command> {
+        let answers = |cmd: &&Command| cmd.get_cmd_name() == name;
+        let aliased = |cmd: &&Command| cmd.get_aliases().iter().any(|alias| alias == name);
+        if commands.len() == self.commands_len {
+            let cmd = commands.get(self.command(name)?)?;
+            if answers(&cmd) || aliased(&cmd) {
+                return Some(cmd);
+            }
+        }
+        commands
+            .iter()
+            .find(answers)
+            .or_else(|| commands.iter().find(aliased))
+    }
+
+    /// The flag in `flags` with long name `name`.
+    pub fn find_flag<'a>(&self, flags: &'a [Flag], name: &str) -> Option<&'a Flag> {
+        if flags.len() == self.flags_len {
+            let flag = flags.get(self.long_flag(name)?)?;
+            if flag.name == name {
+                return Some(flag);
+            }
+        }
+        flags.iter().find(|flag| flag.name == name)
+    }
+
+    /// The flag in `flags` with short name `short`.
+    pub fn find_short_flag<'a>(&self, flags: &'a [Flag], short: &str) -> Option<&'a Flag> {
+        if flags.len() == self.flags_len {
+            let flag = flags.get(self.short_flag(short)?)?;
+            if flag.short == short {
+                return Some(flag);
+            }
+        }
+        flags.iter().find(|flag| flag.short == short)
+    }
+}
+
+#[derive(Default)]
+pub struct FlexCommand {
+    pub name: String,
+    pub desc: String,
+    pub docs: Box<HelpDocs>,
+    pub aliases: Vec<String>,
+    pub action: Option<Action>,
+    pub sub_commands: Vec<Command>,
+    /// Subcommand run when none is named.
+    pub default_subcommand: Option<String>,
+    pub args: Vec<Arg>,
+    pub flags: Vec<Flag>,
+    /// Whether missing required arguments without their own prompt are asked
+    /// for as text.
+    pub prompt: bool,
+    /// Whether long output goes through the pager, `None` to follow the app.
+    pub pager: Option<bool>,
+    pub hooks: Box<Hooks>,
+    pub(crate) index: OnceCell<Box<CommandIndex>>,
+}
+
+/// Help sections beyond a command's one-line description.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct HelpDocs {
+    /// Longer description shown by `--help` in place of `desc`.
+    pub long_about: String,
+    /// Example command lines with what each one does.
+    pub examples: Vec<(String, String)>,
+    /// Footer shown at the end of the help.
+    pub after_help: String,
+    /// Heading the command is listed under in its parent's help, empty for
+    /// none.
+    pub category: String,
+}
+
+impl FlexCommand {
+    pub fn index(&self) -> &CommandIndex {
+        self.index
+            .get_or_init(|| Box::new(CommandIndex::new(&self.sub_commands, &self.flags)))
+    }
+
+    pub fn find_subcommand(&self, name: &str) -> Option<&Command> {
+        self.index().find_command(&self.sub_commands, name)
+    }
+
+    /// The default subcommand `args` run, when they start with a flag or are
+    /// empty.
+    pub(crate) fn default_subcommand_for(&self, args: &[String]) -> Option<&Command> {
+        if args.first().is_some_and(|arg| !arg.starts_with('-')) {
+            return None;
+        }
+        self.find_subcommand(self.default_subcommand.as_deref()?)
+    }
+
+    pub fn find_flag(&self, name: &str) -> Option<&Flag> {
+        self.index().find_flag(&self.flags, name)
+    }
+
+    pub fn find_short_flag(&self, short: &str) -> Option<&Flag> {
+        self.index().find_short_flag(&self.flags, short)
+    }
+}
+
+impl Debug for FlexCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FlexCommand")
+            .field("name", &self.name)
+            .field("desc", &self.desc)
+            .field("docs", &self.docs)
+            .field("aliases", &self.aliases)
+            .field("action", &"<function>")
+            .field("sub_commands", &self.sub_commands)
+            .field("default_subcommand", &self.default_subcommand)
+            .field("args", &self.args)
+            .field("flags", &self.flags)
+            .field("prompt", &self.prompt)
+            .field("pager", &self.pager)
+            .field("hooks", &(self.hooks.before.len() + self.hooks.after.len()))
+            .finish()
+    }
+}
+
+impl Clone for FlexCommand {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            desc: self.desc.clone(),
+            docs: self.docs.clone(),
+            aliases: self.aliases.clone(),
+            action: match self.action {
+                Some(Action::Help) => Some(Action::Help),
+                _ => None,
+            },
+            sub_commands: self.sub_commands.clone(),
+            default_subcommand: self.default_subcommand.clone(),
+            args: self.args.clone(),
+            flags: self.flags.clone(),
+            prompt: self.prompt,
+            pager: self.pager,
+            hooks: self.hooks.clone(),
+            index: OnceCell::new(),
+        }
+    }
+}
+
+impl PartialEq for FlexCommand {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.desc == other.desc
+            && self.docs == other.docs
+            && self.aliases == other.aliases
+            && self.sub_commands == other.sub_commands
+            && self.default_subcommand == other.default_subcommand
+            && self.args == other.args
+            && self.flags == other.flags
+            && self.prompt == other.prompt
+            && self.pager == other.pager
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CommandError {
+    InvalidCommand(String),
+    InvalidArgument(String),
+    MissingSubcommand(String),
+    InvalidConfiguration(String),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::InvalidCommand(e) => write!(f, "Unknown subcommand: {}", e),
+            CommandError::InvalidArgument(e) => write!(f, "Invalid argument: {}", e),
+            CommandError::MissingSubcommand(e) => write!(f, "Missing subcommand: {}", e),
+            CommandError::InvalidConfiguration(e) => {
+                write!(f, "Invalid configuration: {}", e)
+            }
+        }
+    }
+}
+
+impl Error for CommandError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Default { name: String },
+    Flex(FlexCommand),
+}
+
+impl Default for Command {
+    fn default() -> Self {
+        Command::Default {
+            name: String::new(),
+        }
+    }
+}
+
+pub trait Flex {
+    fn description(self, desc: impl Into<String>) -> Self;
+    /// A longer description shown by `--help` and the `help` subcommand;
+    /// `-h` keeps to the one-line [`Flex::description`].
+    fn long_about(self, text: impl Into<String>) -> Self;
+    /// Adds an example to the help, e.g.
+    /// `.example("flex repo add ~/code/x --all", "track one repo")`.
+    fn example(self, command: impl Into<String>, desc: impl Into<String>) -> Self;
+    /// A footer printed at the end of the help.
+    fn after_help(self, text: impl Into<String>) -> Self;
+    /// Lists the command under heading `category` in its parent's help.
+    /// Categories appear in the order their first command was added, followed
+    /// by the commands without one.
+    fn category(self, category: impl Into<String>) -> Self;
+    fn alias(self, alias: impl Into<String>) -> Self;
+    fn subcommand(self, subcmd: Command) -> Self;
+    /// Runs subcommand `name` when the command line names no subcommand,
+    /// passing it any flags given instead, e.g. `flex repo` as `flex repo list`.
+    fn default_subcommand(self, name: impl Into<String>) -> Self;
+    fn action<F, E>(self, action: F) -> Self
+    where
+        F: Fn(&[String]) -> Result<String, E> + 'static,
+        E: Error + 'static;
+    fn typed_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(T) -> Result<String, Box<dyn Error>> + 'static;
+    /// Like [`Flex::typed_action`], also passing the app's shared [`State`].
+    /// Use `T = Matches` to get the parsed arguments as they are.
+    fn state_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(&State, T) -> Result<String, Box<dyn Error>> + 'static;
+    /// Like [`Flex::state_action`], returning structured [`Output`] that
+    /// `--output` renders as text, JSON, YAML or CSV.
+    fn output_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(&State, T) -> Result<Output, Box<dyn Error>> + 'static;
+    /// Like [`Flex::typed_action`], but the action writes to `out` as it goes
+    /// (standard output when run through an app) rather than returning its
+    /// output at the end.
+    fn stream_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(T, &mut dyn Write) -> Streamed + 'static;
+    /// Like [`Flex::action`] for an async closure, which gets its own copy of
+    /// the arguments. Runs on the app's executor.
+    ///
+    /// ```
+    /// use flex::{App, Command, CommandError, Flex};
+    ///
+    /// let scan = Command::default("scan")
+    ///     .subcommand(Command::default("repos").async_action(|repos| async move {
+    ///         Ok::<_, CommandError>(format!("scanned {}", repos.join(", ")))
+    ///     }))
+    ///     .subcommand(Command::default("status").action(|_| Ok::<_, CommandError>("clean".to_string())));
+    /// let app = App::new("flex").add_command(scan);
+    /// let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    ///
+    /// assert_eq!(app.run_from(args(&["scan", "repos", "a", "b"])).unwrap(), "scanned a, b");
+    /// assert_eq!(app.run_from(args(&["scan", "status"])).unwrap(), "clean");
+    /// ```
+    #[cfg(feature = "async")]
+    fn async_action<F, Fut, E>(self, action: F) -> Self
+    where
+        F: Fn(Vec<String>) -> Fut + 'static,
+        Fut: Future<Output = Result<String, E>> + 'static,
+        E: Error + 'static;
+    /// Like [`Flex::typed_action`] for an async closure. Runs on the app's
+    /// executor.
+    #[cfg(feature = "async")]
+    fn async_typed_action<T, F, Fut>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(T) -> Fut + 'static,
+        Fut: Future<Output = Result<String, Box<dyn Error>>> + 'static;
+    fn arg(self, arg: Arg) -> Self;
+    fn flag(self, flag: Flag) -> Self;
+    fn help(self) -> Self;
+    /// Prompts for every missing required argument on an interactive terminal,
+    /// unless `--no-input` is given before the command name.
+    fn prompt_missing(self) -> Self;
+    /// Overrides the app's [`App::pager`](crate::App::pager) setting for this
+    /// command and its subcommands.
+    fn pager(self, enabled: bool) -> Self;
+    /// Runs `hook` before the action of this command or any subcommand; an error
+    /// stops the action from running.
+    fn before<F>(self, hook: F) -> Self
+    where
+        F: Fn(&Invocation) -> Result<(), Box<dyn Error>> + 'static;
+    /// Runs `hook` with the result of the action of this command or any
+    /// subcommand.
+    fn after<F>(self, hook: F) -> Self
+    where
+        F: Fn(&Invocation, &Outcome) + 'static;
+    fn run(&self, args: &[String]) -> Result<String, Box<dyn Error>>;
+}
+
+impl Flex for Command {
+    fn description(self, desc: impl Into<String>) -> Self {
+        self.flex_with(|cmd| {
+            cmd.desc = desc.into();
+        })
+    }
+
+    fn long_about(self, text: impl Into<String>) -> Self {
+        self.flex_with(|cmd| {
+            cmd.docs.long_about = text.into();
+        })
+    }
+
+    fn example(self, command: impl Into<String>, desc: impl Into<String>) -> Self {
+        self.flex_with(|cmd| {
+            cmd.docs.examples.push((command.into(), desc.into()));
+        })
+    }
+
+    fn after_help(self, text: impl Into<String>) -> Self {
+        self.flex_with(|cmd| {
+            cmd.docs.after_help = text.into();
+        })
+    }
+
+    fn category(self, category: impl Into<String>) -> Self {
+        self.flex_with(|cmd| {
+            cmd.docs.category = category.into();
+        })
+    }
+
+    fn alias(self, alias: impl Into<String>) -> Self {
+        self.flex_with(|cmd| {
+            cmd.aliases.push(alias.into());
+        })
+    }
+
+    fn subcommand(self, subcmd: Command) -> Self {
+        self.flex_with(|cmd| {
+            cmd.sub_commands.push(subcmd);
+        })
+    }
+
+    fn default_subcommand(self, name: impl Into<String>) -> Self {
+        self.flex_with(|cmd| {
+            cmd.default_subcommand = Some(name.into());
+        })
+    }
+
+    fn action<F, E>(self, action: F) -> Self
+    where
+        F: Fn(&[String]) -> Result<String, E> + 'static,
+        E: Error + 'static,
+    {
+        let new_action = Box::new(move |args: &[String]| {
+            action(args)
+                .map(Into::into)
+                .map_err(|e| Box::new(e) as Box<dyn Error>)
+        });
+        self.flex_with(|cmd| {
+            cmd.action = Some(Action::Args(new_action));
+        })
+    }
+
+    fn typed_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(T) -> Result<String, Box<dyn Error>> + 'static,
+    {
+        let new_action =
+            Box::new(move |matches: &Matches| Ok(action(T::from_matches(matches)?)?.into()));
+        self.flex_with(|cmd| {
+            cmd.action = Some(Action::Matches(new_action));
+        })
+    }
+
+    fn state_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(&State, T) -> Result<String, Box<dyn Error>> + 'static,
+    {
+        let new_action = Box::new(move |state: &State, matches: &Matches| {
+            Ok(action(state, T::from_matches(matches)?)?.into())
+        });
+        self.flex_with(|cmd| {
+            cmd.action = Some(Action::State(new_action));
+        })
+    }
+
+    fn output_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(&State, T) -> Result<Output, Box<dyn Error>> + 'static,
+    {
+        let new_action =
+            Box::new(move |state: &State, matches: &Matches| action(state, T::from_matches(matches)?));
+        self.flex_with(|cmd| {
+            cmd.action = Some(Action::State(new_action));
+        })
+    }
+
+    fn stream_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(T, &mut dyn Write) -> Streamed + 'static,
+    {
+        let new_action = Box::new(move |matches: &Matches, out: &mut dyn Write| {
+            action(T::from_matches(matches)?, out)
+        });
+        self.flex_with(|cmd| {
+            cmd.action = Some(Action::Stream(new_action));
+        })
+    }
+
+    #[cfg(feature = "async")]
+    fn async_action<F, Fut, E>(self, action: F) -> Self
+    where
+        F: Fn(Vec<String>) -> Fut + 'static,
+        Fut: Future<Output = Result<String, E>> + 'static,
+        E: Error + 'static,
+    {
+        let new_action = Box::new(move |args: &[String]| {
+            let future = action(args.to_vec());
+            Box::pin(async move {
+                match future.await {
+                    Ok(output) => Ok(output.into()),
+                    Err(e) => Err(Box::new(e) as Box<dyn Error>),
+                }
+            })
+                as BoxFuture
+        });
+        self.flex_with(|cmd| {
+            cmd.action = Some(Action::AsyncArgs(new_action));
+        })
+    }
+
+    #[cfg(feature = "async")]
+    fn async_typed_action<T, F, Fut>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(T) -> Fut + 'static,
+        Fut: Future<Output = Result<String, Box<dyn Error>>> + 'static,
+    {
+        let new_action = Box::new(move |matches: &Matches| {
+            let future = T::from_matches(matches).map(&action);
+            Box::pin(async move { Ok(future?.await?.into()) }) as BoxFuture
+        });
+        self.flex_with(|cmd| {
+            cmd.action = Some(Action::AsyncMatches(new_action));
+        })
+    }
+
+    fn arg(self, arg: Arg) -> Self {
+        self.flex_with(|cmd| {
+            cmd.args.push(arg);
+        })
+    }
+
+    fn flag(self, flag: Flag) -> Self {
+        self.flex_with(|cmd| {
+            cmd.flags.push(flag);
+        })
+    }
+
+    fn help(self) -> Self {
+        self.flex_with(|cmd| {
+            cmd.sub_commands.push(Command::help_command());
+        })
+    }
+
+    fn prompt_missing(self) -> Self {
+        self.flex_with(|cmd| {
+            cmd.prompt = true;
+        })
+    }
+
+    fn pager(self, enabled: bool) -> Self {
+        self.flex_with(|cmd| {
+            cmd.pager = Some(enabled);
+        })
+    }
+
+    fn before<F>(self, hook: F) -> Self
+    where
+        F: Fn(&Invocation) -> Result<(), Box<dyn Error>> + 'static,
+    {
+        self.flex_with(|cmd| {
+            cmd.hooks.before.push(Rc::new(hook));
+        })
+    }
+
+    fn after<F>(self, hook: F) -> Self
+    where
+        F: Fn(&Invocation, &Outcome) + 'static,
+    {
+        self.flex_with(|cmd| {
+            cmd.hooks.after.push(Rc::new(hook));
+        })
+    }
+
+    fn run(&self, args: &[String]) -> Result<String, Box<dyn Error>> {
+        Ok(run_command(self, args, &RunContext::default())?.render(OutputFormat::Text))
+    }
+}
+
+impl Flex for &mut Command {
+    fn description(self, desc: impl Into<String>) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.desc = desc.into();
+        });
+        self
+    }
+
+    fn long_about(self, text: impl Into<String>) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.docs.long_about = text.into();
+        });
+        self
+    }
+
+    fn example(self, command: impl Into<String>, desc: impl Into<String>) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.docs.examples.push((command.into(), desc.into()));
+        });
+        self
+    }
+
+    fn after_help(self, text: impl Into<String>) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.docs.after_help = text.into();
+        });
+        self
+    }
+
+    fn category(self, category: impl Into<String>) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.docs.category = category.into();
+        });
+        self
+    }
+
+    fn alias(self, alias: impl Into<String>) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.aliases.push(alias.into());
+        });
+        self
+    }
+
+    fn subcommand(self, subcmd: Command) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.sub_commands.push(subcmd);
+        });
+        self
+    }
+
+    fn default_subcommand(self, name: impl Into<String>) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.default_subcommand = Some(name.into());
+        });
+        self
+    }
+
+    fn action<F, E>(self, action: F) -> Self
+    where
+        F: Fn(&[String]) -> Result<String, E> + 'static,
+        E: Error + 'static,
+    {
+        let new_action = Box::new(move |args: &[String]| {
+            action(args)
+                .map(Into::into)
+                .map_err(|e| Box::new(e) as Box<dyn Error>)
+        });
+        self.flex_mut_with(|cmd| {
+            cmd.action = Some(Action::Args(new_action));
+        });
+        self
+    }
+
+    fn typed_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(T) -> Result<String, Box<dyn Error>> + 'static,
+    {
+        let new_action =
+            Box::new(move |matches: &Matches| Ok(action(T::from_matches(matches)?)?.into()));
+        self.flex_mut_with(|cmd| {
+            cmd.action = Some(Action::Matches(new_action));
+        });
+        self
+    }
+
+    fn state_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(&State, T) -> Result<String, Box<dyn Error>> + 'static,
+    {
+        let new_action = Box::new(move |state: &State, matches: &Matches| {
+            Ok(action(state, T::from_matches(matches)?)?.into())
+        });
+        self.flex_mut_with(|cmd| {
+            cmd.action = Some(Action::State(new_action));
+        });
+        self
+    }
+
+    fn output_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(&State, T) -> Result<Output, Box<dyn Error>> + 'static,
+    {
+        let new_action =
+            Box::new(move |state: &State, matches: &Matches| action(state, T::from_matches(matches)?));
+        self.flex_mut_with(|cmd| {
+            cmd.action = Some(Action::State(new_action));
+        });
+        self
+    }
+
+    fn stream_action<T, F>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(T, &mut dyn Write) -> Streamed + 'static,
+    {
+        let new_action = Box::new(move |matches: &Matches, out: &mut dyn Write| {
+            action(T::from_matches(matches)?, out)
+        });
+        self.flex_mut_with(|cmd| {
+            cmd.action = Some(Action::Stream(new_action));
+        });
+        self
+    }
+
+    #[cfg(feature = "async")]
+    fn async_action<F, Fut, E>(self, action: F) -> Self
+    where
+        F: Fn(Vec<String>) -> Fut + 'static,
+        Fut: Future<Output = Result<String, E>> + 'static,
+        E: Error + 'static,
+    {
+        let new_action = Box::new(move |args: &[String]| {
+            let future = action(args.to_vec());
+            Box::pin(async move {
+                match future.await {
+                    Ok(output) => Ok(output.into()),
+                    Err(e) => Err(Box::new(e) as Box<dyn Error>),
+                }
+            })
+                as BoxFuture
+        });
+        self.flex_mut_with(|cmd| {
+            cmd.action = Some(Action::AsyncArgs(new_action));
+        });
+        self
+    }
+
+    #[cfg(feature = "async")]
+    fn async_typed_action<T, F, Fut>(self, action: F) -> Self
+    where
+        T: FromMatches + 'static,
+        F: Fn(T) -> Fut + 'static,
+        Fut: Future<Output = Result<String, Box<dyn Error>>> + 'static,
+    {
+        let new_action = Box::new(move |matches: &Matches| {
+            let future = T::from_matches(matches).map(&action);
+            Box::pin(async move { Ok(future?.await?.into()) }) as BoxFuture
+        });
+        self.flex_mut_with(|cmd| {
+            cmd.action = Some(Action::AsyncMatches(new_action));
+        });
+        self
+    }
+
+    fn arg(self, arg: Arg) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.args.push(arg);
+        });
+        self
+    }
+
+    fn flag(self, flag: Flag) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.flags.push(flag);
+        });
+        self
+    }
+
+    fn help(self) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.sub_commands.push(Command::help_command());
+        });
+        self
+    }
+
+    fn prompt_missing(self) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.prompt = true;
+        });
+        self
+    }
+
+    fn pager(self, enabled: bool) -> Self {
+        self.flex_mut_with(|cmd| {
+            cmd.pager = Some(enabled);
+        });
+        self
+    }
+
+    fn before<F>(self, hook: F) -> Self
+    where
+        F: Fn(&Invocation) -> Result<(), Box<dyn Error>> + 'static,
+    {
+        self.flex_mut_with(|cmd| {
+            cmd.hooks.before.push(Rc::new(hook));
+        });
+        self
+    }
+
+    fn after<F>(self, hook: F) -> Self
+    where
+        F: Fn(&Invocation, &Outcome) + 'static,
+    {
+        self.flex_mut_with(|cmd| {
+            cmd.hooks.after.push(Rc::new(hook));
+        });
+        self
+    }
+
+    fn run(&self, args: &[String]) -> Result<String, Box<dyn Error>> {
+        Ok(run_command(self, args, &RunContext::default())?.render(OutputFormat::Text))
+    }
+}
+
+impl Command {
+    pub fn default(name: impl Into<String>) -> Self {
+        Command::Default { name: name.into() }
+    }
+
+    pub fn flex(cmd: FlexCommand) -> Self {
+        Command::Flex(cmd)
+    }
+
+    fn flex_with<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut FlexCommand),
+    {
+        match self {
+            Command::Default { name } => {
+                let mut cmd = FlexCommand {
+                    name: name.to_string(),
+                    ..Default::default()
+                };
+                f(&mut cmd);
+                Command::Flex(cmd)
+            }
+            Command::Flex(mut cmd) => {
+                f(&mut cmd);
+                cmd.index.take();
+                Command::Flex(cmd)
+            }
+        }
+    }
+
+    fn flex_mut_with<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut FlexCommand),
+    {
+        *self = match std::mem::take(self) {
+            Command::Default { name } => {
+                let mut cmd = FlexCommand {
+                    name: name.to_string(),
+                    ..Default::default()
+                };
+                f(&mut cmd);
+                Command::Flex(cmd)
+            }
+            Command::Flex(mut cmd) => {
+                f(&mut cmd);
+                cmd.index.take();
+                Command::Flex(cmd)
+            }
+        };
+    }
+
+    /// A `help` subcommand that renders the help of whichever command it is
+    /// attached to.
+    pub fn help_command() -> Command {
+        Command::flex(FlexCommand {
+            name: "help".to_string(),
+            action: Some(Action::Help),
+            ..Default::default()
+        })
+    }
+
+    pub fn is_help(&self) -> bool {
+        matches!(
+            self,
+            Command::Flex(FlexCommand {
+                action: Some(Action::Help),
+                ..
+            })
+        )
+    }
+
+    pub fn get_cmd_name(&self) -> &str {
+        match self {
+            Command::Default { name } => name,
+            Command::Flex(cmd) => &cmd.name,
+        }
+    }
+
+    pub fn get_cmd_description(&self) -> &str {
+        match self {
+            Command::Default { .. } => "",
+            Command::Flex(cmd) => &cmd.desc,
+        }
+    }
+
+    pub fn get_category(&self) -> &str {
+        match self {
+            Command::Default { .. } => "",
+            Command::Flex(cmd) => &cmd.docs.category,
+        }
+    }
+
+    pub fn get_aliases(&self) -> &[String] {
+        match self {
+            Command::Default { .. } => &[],
+            Command::Flex(cmd) => &cmd.aliases,
+        }
+    }
+
+    pub fn get_available_cmds(&self) -> &[Command] {
+        match self {
+            Command::Default { .. } => &[],
+            Command::Flex(cmd) => &cmd.sub_commands,
+        }
+    }
+
+    pub fn get_args(&self) -> &[Arg] {
+        match self {
+            Command::Default { .. } => &[],
+            Command::Flex(cmd) => &cmd.args,
+        }
+    }
+
+    pub fn get_flags(&self) -> &[Flag] {
+        match self {
+            Command::Default { .. } => &[],
+            Command::Flex(cmd) => &cmd.flags,
+        }
+    }
+
+    /// Looks up a subcommand by name or alias.
+    pub fn find_subcommand(&self, name: &str) -> Option<&Command> {
+        match self {
+            Command::Default { .. } => None,
+            Command::Flex(cmd) => cmd.find_subcommand(name),
+        }
+    }
+
+    /// Gives every argument and flag in this command tree that has no explicit
+    /// environment variable one derived from its name, e.g. `FLEX_REPO_PATH` for
+    /// `repo-path` with prefix `FLEX`.
+    pub fn apply_env_prefix(&mut self, prefix: &str) {
+        let env_name = |name: &str| format!("{}_{}", prefix, name.to_uppercase().replace('-', "_"));
+        if let Command::Flex(cmd) = self {
+            for arg in cmd.args.iter_mut().filter(|arg| arg.env.is_empty()) {
+                arg.env = env_name(&arg.name);
+            }
+            for flag in cmd.flags.iter_mut().filter(|flag| flag.env.is_empty()) {
+                flag.env = env_name(&flag.name);
+            }
+            for sub_cmd in cmd.sub_commands.iter_mut() {
+                sub_cmd.apply_env_prefix(prefix);
+            }
+        }
+    }
+
+    /// Builds the lookup index of this command and every subcommand now rather
+    /// than on first dispatch. Modifying the tree afterwards through the
+    /// builder methods drops the affected index again.
+    pub fn finalize(&self) {
+        if let Command::Flex(cmd) = self {
+            cmd.index();
+            for sub_cmd in &cmd.sub_commands {
+                sub_cmd.finalize();
+            }
+        }
+    }
+
+    /// Walks this command and its subcommands and returns every configuration
+    /// problem found, each prefixed with the command path. `path` is the
+    /// path of this command, e.g. `flex repo`.
+    pub fn validate(&self, path: &str) -> Vec<String> {
+        let mut problems = duplicate_names(path, self.get_available_cmds());
+
+        let flags = self.get_flags();
+        for (i, flag) in flags.iter().enumerate() {
+            let earlier = &flags[..i];
+            if earlier.iter().any(|other| other.name == flag.name) {
+                problems.push(format!("{}: duplicate flag '--{}'", path, flag.name));
+            }
+            if !flag.short.is_empty()
+                && let Some(other) = earlier.iter().find(|other| other.short == flag.short)
+            {
+                problems.push(format!(
+                    "{}: flags '--{}' and '--{}' share short flag '-{}'",
+                    path, other.name, flag.name, flag.short
+                ));
+            }
+            if let Some(negated) = flag.name.strip_prefix("no-")
+                && flags.iter().any(|other| other.name == negated && other.default)
+            {
+                problems.push(format!(
+                    "{}: flag '--{}' clashes with the negation of '--{}'",
+                    path, flag.name, negated
+                ));
+            }
+        }
+
+        let args = self.get_args();
+        for arg in args.iter().rev().skip(1).filter(|arg| arg.multiple) {
+            problems.push(format!(
+                "{}: argument <{}> takes multiple values but is not the last argument",
+                path, arg.name
+            ));
+        }
+        for arg in args {
+            if let Some(Prompt::Select(choices)) = &arg.prompt
+                && choices.is_empty()
+            {
+                problems.push(format!(
+                    "{}: argument <{}> prompts to select from no values",
+                    path, arg.name
+                ));
+            }
+            if let Some(max) = arg.max
+                && max < arg.min_count()
+            {
+                problems.push(format!(
+                    "{}: argument <{}> allows at most {} values but requires at least {}",
+                    path,
+                    arg.name,
+                    max,
+                    arg.min_count()
+                ));
+            }
+        }
+        if let Some(optional) = args.iter().position(|arg| !arg.required) {
+            for arg in args[optional..].iter().filter(|arg| arg.required) {
+                problems.push(format!(
+                    "{}: required argument <{}> is declared after optional argument <{}>",
+                    path, arg.name, args[optional].name
+                ));
+            }
+        }
+
+        if let Command::Flex(FlexCommand {
+            default_subcommand: Some(name),
+            ..
+        }) = self
+            && self.find_subcommand(name).is_none()
+        {
+            problems.push(format!(
+                "{}: default subcommand '{}' does not exist",
+                path, name
+            ));
+        }
+
+        for sub_cmd in self.get_available_cmds() {
+            problems.extend(sub_cmd.validate(&format!("{} {}", path, sub_cmd.get_cmd_name())));
+        }
+        problems
+    }
+
+    /// Parses `args` against this command's declared arguments, flags and
+    /// subcommands without running any action.
+    ///
+    /// Follows the POSIX conventions: everything after `--` is a positional
+    /// value, a negative number such as `-5` is a value unless the command has a
+    /// matching short flag, and a lone `-` is a value meaning standard input.
+    ///
+    /// ```
+    /// use flex::{Arg, Command, Flag, Flex};
+    ///
+    /// let cmd = Command::default("exec")
+    ///     .arg(Arg::new("offset"))
+    ///     .arg(Arg::new("input"))
+    ///     .flag(Flag::new("verbose").short("v"));
+    /// let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    ///
+    /// let matches = cmd.parse(&args(&["-v", "-5", "-"])).unwrap();
+    /// assert!(matches.is_present("verbose"));
+    /// assert_eq!(matches.value("offset"), Some("-5"));
+    /// assert!(matches.is_stdin("input"));
+    ///
+    /// let matches = cmd.parse(&args(&["--", "-v", "--verbose"])).unwrap();
+    /// assert!(!matches.is_present("verbose"));
+    /// assert_eq!(matches.value("offset"), Some("-v"));
+    /// assert_eq!(matches.value("input"), Some("--verbose"));
+    /// ```
+    pub fn parse(&self, args: &[String]) -> Result<Matches, CommandError> {
+        self.parse_with(args, true)
+    }
+
+    /// Like [`Command::parse`], prompting for missing arguments only when
+    /// `prompts` is set.
+    pub(crate) fn parse_with(&self, args: &[String], prompts: bool) -> Result<Matches, CommandError> {
+        let mut matches = Matches::new(self.get_cmd_name());
+        let Command::Flex(cmd) = self else {
+            if let Some(arg) = args.first() {
+                return Err(CommandError::InvalidArgument(format!(
+                    "Command '{}' takes no arguments, got '{}'",
+                    matches.name, arg
+                )));
+            }
+            return Ok(matches);
+        };
+
+        let rest_at = cmd.args.iter().position(|arg| arg.rest);
+        let mut positionals: Vec<String> = Vec::new();
+        let mut options_ended = false;
+        let mut rest = args.iter().enumerate();
+        while let Some((i, arg)) = rest.next() {
+            if options_ended || rest_at.is_some_and(|at| positionals.len() > at) {
+                positionals.push(arg.clone());
+            } else if arg == "--" {
+                options_ended = true;
+            } else if let Some(long) = arg.strip_prefix("--") {
+                let (long, inline) = match long.split_once('=') {
+                    Some((long, value)) => (long, Some(value)),
+                    None => (long, None),
+                };
+                if let Some(flag) = cmd.find_flag(long) {
+                    if flag.map {
+                        let pair = match inline {
+                            Some(pair) => pair,
+                            None => flag_value(flag, rest.next().map(|(_, arg)| arg))?,
+                        };
+                        insert_pair(&mut matches, flag, pair, ValueSource::CommandLine)?;
+                    } else if inline.is_some() {
+                        return Err(CommandError::InvalidArgument(format!(
+                            "Flag '--{}' takes no value, got '{}'",
+                            flag.name, arg
+                        )));
+                    } else {
+                        matches.insert_flag(&flag.name, ValueSource::CommandLine);
+                    }
+                } else if let Some(flag) = long
+                    .strip_prefix("no-")
+                    .and_then(|name| cmd.find_flag(name))
+                    .filter(|flag| flag.default && inline.is_none())
+                {
+                    matches.clear_flag(&flag.name, ValueSource::CommandLine);
+                } else {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Command '{}' has no flag '--{}'",
+                        cmd.name, long
+                    )));
+                }
+            } else if let Some(shorts) = arg
+                .strip_prefix('-')
+                .filter(|s| !s.is_empty() && !is_negative_number(cmd, s))
+            {
+                for (at, short) in shorts.char_indices() {
+                    let flag = cmd
+                        .find_short_flag(short.encode_utf8(&mut [0; 4]))
+                        .ok_or_else(|| {
+                            CommandError::InvalidArgument(format!(
+                                "Command '{}' has no flag '-{}'",
+                                cmd.name, short
+                            ))
+                        })?;
+                    if flag.map {
+                        // The rest of the bundle, or else the next word, is the pair.
+                        let attached = &shorts[at + short.len_utf8()..];
+                        let pair = match attached {
+                            "" => flag_value(flag, rest.next().map(|(_, arg)| arg))?,
+                            attached => attached,
+                        };
+                        insert_pair(&mut matches, flag, pair, ValueSource::CommandLine)?;
+                        break;
+                    }
+                    matches.insert_flag(&flag.name, ValueSource::CommandLine);
+                }
+            } else if let Some(sub_cmd) =
+                cmd.find_subcommand(arg).filter(|_| positionals.is_empty())
+            {
+                matches.subcommand = Some(Box::new(sub_cmd.parse_with(&args[i + 1..], prompts)?));
+                break;
+            } else {
+                positionals.push(arg.clone());
+            }
+        }
+
+        let (singles, variadic) = match cmd.args.split_last() {
+            Some((last, singles)) if last.multiple => (singles, Some(last)),
+            _ => (cmd.args.as_slice(), None),
+        };
+        if variadic.is_none() && positionals.len() > cmd.args.len() {
+            return Err(CommandError::InvalidArgument(format!(
+                "Command '{}' takes {} arguments, got {}",
+                cmd.name,
+                cmd.args.len(),
+                positionals.len()
+            )));
+        }
+        let mut positionals = positionals.into_iter();
+        for (arg, value) in singles.iter().zip(positionals.by_ref()) {
+            matches.insert_value(&arg.name, value, ValueSource::CommandLine);
+        }
+        if let Some(arg) = variadic {
+            for value in positionals {
+                matches.insert_value(&arg.name, value, ValueSource::CommandLine);
+            }
+        }
+        for arg in &cmd.args {
+            if matches.source(&arg.name).is_some() {
+                continue;
+            }
+            if let Some(value) = env_value(&arg.env) {
+                let values = if arg.multiple {
+                    split_words(&value).map_err(|e| {
+                        CommandError::InvalidArgument(format!(
+                            "Invalid value in environment variable {} for <{}>: {}",
+                            arg.env, arg.name, e
+                        ))
+                    })?
+                } else {
+                    vec![value]
+                };
+                for value in values {
+                    matches.insert_value(&arg.name, value, ValueSource::Env(arg.env.clone()));
+                }
+            } else if let Some((values, file)) = &arg.config {
+                for value in values {
+                    matches.insert_value(&arg.name, value, ValueSource::Config(file.clone()));
+                }
+            } else if let Some(default) = &arg.default {
+                matches.insert_value(&arg.name, default, ValueSource::Default);
+            } else if arg.required
+                && prompts
+                && let Some(value) = prompt_value(cmd, arg)?
+            {
+                matches.insert_value(&arg.name, value, ValueSource::Prompt);
+            } else if arg.required {
+                return Err(CommandError::InvalidArgument(format!(
+                    "Command '{}' requires argument <{}>",
+                    cmd.name, arg.name
+                )));
+            }
+        }
+        // Counted once every layer is in, whichever one the values came from.
+        for arg in cmd.args.iter().filter(|arg| arg.multiple) {
+            let Some(source) = matches.source(&arg.name) else {
+                continue;
+            };
+            let count = matches.values_of(&arg.name).len();
+            if let Some(max) = arg.max
+                && count > max
+            {
+                return Err(CommandError::InvalidArgument(format!(
+                    "Command '{}' takes at most {} values for <{}>, got {} from the {}",
+                    cmd.name, max, arg.name, count, source
+                )));
+            }
+            if count < arg.min_count() {
+                return Err(CommandError::InvalidArgument(format!(
+                    "Command '{}' requires at least {} values for <{}>, got {} from the {}",
+                    cmd.name,
+                    arg.min_count(),
+                    arg.name,
+                    count,
+                    source
+                )));
+            }
+        }
+        for flag in &cmd.flags {
+            if matches.source(&flag.name).is_some() {
+                continue;
+            }
+            let layered = match (env_value(&flag.env), &flag.config) {
+                (Some(value), _) => Some((value, ValueSource::Env(flag.env.clone()))),
+                (None, Some((value, file))) => Some((value.clone(), ValueSource::Config(file.clone()))),
+                (None, None) => None,
+            };
+            let Some((value, source)) = layered else {
+                if flag.default {
+                    matches.insert_flag(&flag.name, ValueSource::Default);
+                }
+                continue;
+            };
+            if flag.map {
+                for pair in value.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+                    insert_pair(&mut matches, flag, pair, source.clone())?;
+                }
+                continue;
+            }
+            let count = match value.to_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => 1,
+                "" | "0" | "false" | "no" | "off" => 0,
+                other => match other.parse::<usize>() {
+                    Ok(count) if flag.counted => count,
+                    _ => {
+                        return Err(CommandError::InvalidArgument(format!(
+                            "Invalid value '{}' in {} for flag '--{}': expected {}",
+                            value,
+                            source,
+                            flag.name,
+                            if flag.counted { "a count" } else { "true or false" }
+                        )));
+                    }
+                },
+            };
+            if count == 0 {
+                matches.clear_flag(&flag.name, source);
+            } else {
+                for _ in 0..count {
+                    matches.insert_flag(&flag.name, source.clone());
+                }
+            }
+        }
+
+        Ok(matches)
+    }
+
+    /// Renders the help text shown by this command's `help` subcommand and by
+    /// `--help`, with the long description and examples. `path` is how the
+    /// command is invoked, e.g. `flex repo add`, and starts the usage line.
+    pub fn render_help(&self, path: &str) -> String {
+        self.help_text(path, true)
+    }
+
+    /// Renders the shorter help shown by `-h`, with a one-line description and
+    /// no examples.
+    pub fn render_short_help(&self, path: &str) -> String {
+        self.help_text(path, false)
+    }
+
+    fn help_text(&self, path: &str, long: bool) -> String {
+        let no_docs = HelpDocs::default();
+        let HelpDocs {
+            long_about,
+            examples,
+            after_help,
+            ..
+        } = match self {
+            Command::Default { .. } => &no_docs,
+            Command::Flex(cmd) => &cmd.docs,
+        };
+        let about = if long && !long_about.is_empty() {
+            long_about
+        } else if self.get_cmd_description().is_empty() {
+            long_about.lines().next().unwrap_or_default()
+        } else {
+            self.get_cmd_description()
+        };
+        let mut usage = path.to_string();
+        if !self.get_flags().is_empty() {
+            usage.push_str(" [<flags>]");
+        }
+        let has_subcommands = self.get_available_cmds().iter().any(|cmd| !cmd.is_help());
+        if has_subcommands || self.get_args().is_empty() {
+            usage.push_str(" <subcommand> [<args>]");
+        } else {
+            for arg in self.get_args() {
+                usage.push(' ');
+                usage.push_str(&arg.usage());
+            }
+        }
+        let mut output = format!("Usage: {}\n", usage);
+        if !about.is_empty() {
+            output.push_str(&format!("{}\n\n", about));
+        }
+        render_command_list(&mut output, "Subcommands", self.get_available_cmds());
+        if !self.get_args().is_empty() {
+            output.push_str("\nArguments:\n");
+            for arg in self.get_args() {
+                output.push_str(&format!(
+                    "  {:<16} {}{}{}\n",
+                    if arg.multiple {
+                        format!("{}...", arg.name)
+                    } else {
+                        arg.name.clone()
+                    },
+                    if arg.required {
+                        "(required)"
+                    } else {
+                        "(optional)"
+                    },
+                    env_hint(&arg.env),
+                    arg.default
+                        .as_ref()
+                        .map(|default| format!(" [default: {}]", default))
+                        .unwrap_or_default()
+                ));
+            }
+        }
+        if !self.get_flags().is_empty() {
+            output.push_str("\nFlags:\n");
+            for flag in self.get_flags() {
+                let (long, short) = if flag.map {
+                    (format!("{} <k=v>...", flag.name), flag.short.clone())
+                } else if flag.counted {
+                    let short = if flag.short.is_empty() {
+                        String::new()
+                    } else {
+                        format!("{}...", flag.short)
+                    };
+                    (format!("{}...", flag.name), short)
+                } else if flag.default {
+                    (format!("[no-]{}", flag.name), flag.short.clone())
+                } else {
+                    (flag.name.clone(), flag.short.clone())
+                };
+                let short = if short.is_empty() { String::new() } else { format!("-{}", short) };
+                let line = format!("  --{:<14} {:<8} {}{}", long, short, flag.desc, env_hint(&flag.env));
+                output.push_str(line.trim_end());
+                output.push('\n');
+            }
+        }
+        if long && !examples.is_empty() {
+            output.push_str("\nExamples:\n");
+            for (command, desc) in examples {
+                output.push_str(&format!("  {}\n", command));
+                if !desc.is_empty() {
+                    output.push_str(&format!("      {}\n", desc));
+                }
+            }
+        }
+        if !after_help.is_empty() {
+            output.push_str(&format!("\n{}\n", after_help));
+        }
+        output.trim().to_string()
+    }
+}
+
+/// Lists `commands` (except `help`) under "Available <noun>:", or under their
+/// categories in first-seen order and then "Other <noun>:" when any has one.
+/// Adds nothing when there are no such commands.
+pub(crate) fn render_command_list(output: &mut String, noun: &str, commands: &[Command]) {
+    let commands: Vec<&Command> = commands.iter().filter(|cmd| !cmd.is_help()).collect();
+    if commands.is_empty() {
+        return;
+    }
+    let mut categories: Vec<&str> = Vec::new();
+    for cmd in &commands {
+        if !cmd.get_category().is_empty() && !categories.contains(&cmd.get_category()) {
+            categories.push(cmd.get_category());
+        }
+    }
+    let mut sections: Vec<(String, Vec<&Command>)> = categories
+        .iter()
+        .map(|category| {
+            let listed = commands
+                .iter()
+                .filter(|cmd| cmd.get_category() == *category)
+                .copied()
+                .collect();
+            (category.to_string(), listed)
+        })
+        .collect();
+    let uncategorized: Vec<&Command> = commands
+        .iter()
+        .filter(|cmd| cmd.get_category().is_empty())
+        .copied()
+        .collect();
+    if categories.is_empty() {
+        sections.push((format!("Available {}", noun), uncategorized));
+    } else if !uncategorized.is_empty() {
+        sections.push((format!("Other {}", noun), uncategorized));
+    }
+    for (i, (heading, listed)) in sections.iter().enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        output.push_str(&format!("{}:\n", heading));
+        for cmd in listed {
+            output.push_str(&format!(
+                "  {:<16} {}\n",
+                cmd.get_cmd_name(),
+                cmd.get_cmd_description()
+            ));
+        }
+    }
+}
+
+/// Reports each name or alias that is used by more than one of `commands`,
+/// e.g. a command that had `.help()` applied twice.
+pub(crate) fn duplicate_names(path: &str, commands: &[Command]) -> Vec<String> {
+    let names: Vec<&str> = commands
+        .iter()
+        .flat_map(|cmd| {
+            std::iter::once(cmd.get_cmd_name())
+                .chain(cmd.get_aliases().iter().map(String::as_str))
+        })
+        .collect();
+    let mut problems = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].iter().filter(|other| *other == name).count() == 1 {
+            problems.push(format!("{}: duplicate subcommand '{}'", path, name));
+        }
+    }
+    problems
+}
+
+/// Asks for missing required argument `arg` when it or `cmd` opts in to
+/// prompting and the terminal is interactive.
+#[cfg(feature = "prompt")]
+fn prompt_value(cmd: &FlexCommand, arg: &Arg) -> Result<Option<String>, CommandError> {
+    let prompt = match &arg.prompt {
+        Some(prompt) => prompt,
+        None if cmd.prompt => &Prompt::Text,
+        None => return Ok(None),
+    };
+    if !super::prompt::is_interactive() {
+        return Ok(None);
+    }
+    prompt.ask(arg).map(Some).map_err(|e| {
+        CommandError::InvalidArgument(format!("Cannot prompt for <{}>: {}", arg.name, e))
+    })
+}
+
+#[cfg(not(feature = "prompt"))]
+fn prompt_value(_: &FlexCommand, _: &Arg) -> Result<Option<String>, CommandError> {
+    Ok(None)
+}
+
+/// The `key=value` word following map flag `flag` on the command line.
+fn flag_value<'a>(flag: &Flag, next: Option<&'a String>) -> Result<&'a str, CommandError> {
+    next.map(String::as_str).ok_or_else(|| {
+        CommandError::InvalidArgument(format!(
+            "Flag '--{}' requires a key=value pair",
+            flag.name
+        ))
+    })
+}
+
+/// Checks `pair` against map flag `flag` and its key checks, then records it.
+fn insert_pair(
+    matches: &mut Matches,
+    flag: &Flag,
+    pair: &str,
+    source: ValueSource,
+) -> Result<(), CommandError> {
+    let origin = match &source {
+        ValueSource::CommandLine => String::new(),
+        source => format!(" in {}", source),
+    };
+    let Some((key, value)) = pair.split_once('=').filter(|(key, _)| !key.is_empty()) else {
+        return Err(CommandError::InvalidArgument(format!(
+            "Invalid value '{}'{} for flag '--{}': expected key=value",
+            pair, origin, flag.name
+        )));
+    };
+    if let Some(key_check) = flag.keys.iter().find(|key_check| key_check.key == key)
+        && let Err(e) = (key_check.check)(value)
+    {
+        return Err(CommandError::InvalidArgument(format!(
+            "Invalid value '{}'{} for key '{}' of flag '--{}': {}",
+            value, origin, key, flag.name, e
+        )));
+    }
+    matches.insert_value(&flag.name, pair, source);
+    Ok(())
+}
+
+/// Whether `-<digits>` is a negative number rather than a bundle of short flags,
+/// i.e. it parses as a number and its first digit is not itself a short flag.
+fn is_negative_number(cmd: &FlexCommand, digits: &str) -> bool {
+    digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && digits.parse::<f64>().is_ok()
+        && cmd.find_short_flag(&digits[..1]).is_none()
+}
+
+/// Reads environment variable `var`, treating an empty name as "no variable".
+fn env_value(var: &str) -> Option<String> {
+    if var.is_empty() {
+        None
+    } else {
+        std::env::var(var).ok()
+    }
+}
+
+fn env_hint(var: &str) -> String {
+    if var.is_empty() {
+        String::new()
+    } else {
+        format!(" [env: {}]", var)
+    }
+}
+
+enum HelpRequest {
+    Long,
+    Short,
+}
+
+/// Whether `args` ask for `cmd`'s help with `--help` or `-h`, unless they name
+/// a subcommand (which answers for itself), the command declares those flags,
+/// or its action takes the raw arguments.
+fn help_request(cmd: &FlexCommand, args: &[String]) -> Option<HelpRequest> {
+    if cmd.action.as_ref().is_some_and(Action::is_raw)
+        || args.first().is_some_and(|first| cmd.find_subcommand(first).is_some())
+    {
+        return None;
+    }
+    args.iter()
+        .take_while(|arg| *arg != "--")
+        .find_map(|arg| match arg.as_str() {
+            "--help" if cmd.find_flag("help").is_none() => Some(HelpRequest::Long),
+            "-h" if cmd.find_short_flag("h").is_none() => Some(HelpRequest::Short),
+            _ => None,
+        })
+}
+
+/// Parses `args` for `cmd` and runs `action` on the result inside the hooks and
+/// middleware. A parse error goes through them too, in place of the action.
+fn run_parsed(
+    cmd: &Command,
+    args: &[String],
+    ctx: &RunContext,
+    action: &dyn Fn(&Matches) -> Outcome,
+) -> Outcome {
+    match cmd.parse_with(args, ctx.prompts()) {
+        Ok(matches) => ctx.run(args, Some(&matches), &|| action(&matches)),
+        Err(e) => ctx.fail(args, e),
+    }
+}
+
+/// Runs `sub_cmd`, or renders `parent`'s help when `sub_cmd` is its `help`.
+fn run_subcommand(
+    parent: &Command,
+    sub_cmd: &Command,
+    args: &[String],
+    ctx: &RunContext,
+) -> Outcome {
+    if sub_cmd.is_help() {
+        Ok(parent.render_help(&ctx.command_path()).into())
+    } else {
+        run_command(sub_cmd, args, ctx)
+    }
+}
+
+/// Runs `cmd` with `args` inside the hooks of its parent commands and the
+/// app's middleware collected in `ctx`.
+pub(crate) fn run_command(
+    cmd: &Command,
+    args: &[String],
+    ctx: &RunContext,
+) -> Outcome {
+    let parent_cmd = cmd.get_cmd_name().to_string();
+    let default_action = move || {
+        Ok(format!(
+            "Command '{}' called (default). Use `action()` to customize or `help()` to add a help subcommand.",
+            parent_cmd
+        )
+        .into())
+    };
+
+    match cmd {
+        Command::Default { name } => ctx.enter(name, None).run(args, None, &default_action),
+        Command::Flex(flex_cmd) => {
+            let ctx = &ctx.enter(&flex_cmd.name, Some(&flex_cmd.hooks));
+            if let Some(Action::Help) = flex_cmd.action {
+                return Ok(cmd.render_help(&ctx.command_path()).into());
+            }
+            match help_request(flex_cmd, args) {
+                Some(HelpRequest::Long) => return Ok(cmd.render_help(&ctx.command_path()).into()),
+                Some(HelpRequest::Short) => return Ok(cmd.render_short_help(&ctx.command_path()).into()),
+                None => {}
+            }
+
+            if flex_cmd.action.as_ref().is_some_and(Action::is_raw) {
+                let required_args: usize = flex_cmd.args.iter().map(Arg::min_count).sum();
+                if args.len() < required_args {
+                    return ctx.fail(
+                        args,
+                        CommandError::InvalidArgument(format!(
+                            "Command '{}' requires {} arguments, got {}",
+                            flex_cmd.name,
+                            required_args,
+                            args.len()
+                        )),
+                    );
+                }
+            }
+
+            if !flex_cmd.sub_commands.is_empty() {
+                // Typed actions may get every argument from the environment or a
+                // default, so an empty command line still runs them.
+                let typed = flex_cmd
+                    .action
+                    .as_ref()
+                    .is_some_and(|action| !action.is_raw() && !matches!(action, Action::Help));
+                // Flags alone still go to the default subcommand, but a word
+                // that is no subcommand is reported rather than passed on.
+                if let Some(sub_cmd) = flex_cmd.default_subcommand_for(args) {
+                    return run_subcommand(cmd, sub_cmd, args, ctx);
+                }
+                if args.is_empty() && !typed {
+                    if let Some(help) = flex_cmd.find_subcommand("help") {
+                        return run_subcommand(cmd, help, args, ctx);
+                    }
+                    return ctx.fail(
+                        args,
+                        CommandError::MissingSubcommand(format!(
+                            "Command '{}' requires a subcommand",
+                            flex_cmd.name
+                        )),
+                    );
+                }
+
+                if let Some((sub_command, sub_command_args)) = args.split_first()
+                    && let Some(sub_cmd) = flex_cmd.find_subcommand(sub_command)
+                {
+                    return run_subcommand(cmd, sub_cmd, sub_command_args, ctx);
+                }
+
+                if flex_cmd.action.is_none() {
+                    return ctx.fail(
+                        args,
+                        CommandError::InvalidCommand(format!("Unknown subcommand: {}", args[0])),
+                    );
+                }
+            }
+
+            match &flex_cmd.action {
+                Some(Action::Args(action)) => return ctx.run(args, None, &|| action(args)),
+                Some(Action::Matches(action)) => {
+                    return run_parsed(cmd, args, ctx, &|matches| action(matches));
+                }
+                Some(Action::State(action)) => {
+                    // Outside an app there is nothing shared yet.
+                    let no_state = State::default();
+                    let state = ctx.state().unwrap_or(&no_state);
+                    return run_parsed(cmd, args, ctx, &|matches| action(state, matches));
+                }
+                Some(Action::Stream(action)) => {
+                    return run_parsed(cmd, args, ctx, &|matches| ctx.stream(|out| action(matches, out)));
+                }
+                #[cfg(feature = "async")]
+                Some(Action::AsyncArgs(action)) => {
+                    return ctx.run(args, None, &|| ctx.block_on(action(args)));
+                }
+                #[cfg(feature = "async")]
+                Some(Action::AsyncMatches(action)) => {
+                    return run_parsed(cmd, args, ctx, &|matches| ctx.block_on(action(matches)));
+                }
+                Some(Action::Help) | None => {}
+            }
+
+            if let Some(help) = flex_cmd.find_subcommand("help") {
+                return run_subcommand(cmd, help, args, ctx);
+            }
+
+            ctx.run(args, None, &default_action)
+        }
+    }
+}
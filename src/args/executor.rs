@@ -0,0 +1,52 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+use super::hooks::Outcome;
+
+pub type BoxFuture = Pin<Box<dyn Future<Output = Outcome>>>;
+
+/// Drives an async action to completion. Implemented for closures, so a
+/// runtime can be plugged in with e.g. `App::executor(move |f| rt.block_on(f))`.
+pub trait Executor {
+    fn block_on(&self, future: BoxFuture) -> Outcome;
+}
+
+impl<F> Executor for F
+where
+    F: Fn(BoxFuture) -> Outcome,
+{
+    fn block_on(&self, future: BoxFuture) -> Outcome {
+        self(future)
+    }
+}
+
+/// Polls the future on the calling thread, parking it while the future waits.
+/// Enough for futures that do not need a runtime's reactor or timers.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CurrentThread;
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+impl Executor for CurrentThread {
+    fn block_on(&self, mut future: BoxFuture) -> Outcome {
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(outcome) => return outcome,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    fmt::{self, Debug},
+    path::PathBuf,
+    rc::Rc,
+};
+
+pub type ValueCheck = Rc<dyn Fn(&str) -> Result<(), String>>;
+
+/// Checks the value given for one key of a map flag, returning why it is
+/// rejected.
+#[derive(Clone)]
+pub struct KeyCheck {
+    pub key: String,
+    pub check: ValueCheck,
+}
+
+impl Debug for KeyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyCheck").field("key", &self.key).finish()
+    }
+}
+
+impl PartialEq for KeyCheck {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && Rc::ptr_eq(&self.check, &other.check)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Flag {
+    pub name: String,
+    pub short: String,
+    pub desc: String,
+    pub env: String,
+    /// Value and file found by the config layer, if any.
+    pub config: Option<(String, PathBuf)>,
+    pub counted: bool,
+    pub default: bool,
+    pub map: bool,
+    pub keys: Vec<KeyCheck>,
+}
+
+impl Flag {
+    pub fn new(name: impl Into<String>) -> Self {
+        Flag {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn flex(flag: Flag) -> Self {
+        Self {
+            name: flag.name,
+            short: flag.short,
+            desc: flag.desc,
+            env: flag.env,
+            config: flag.config,
+            counted: flag.counted,
+            default: flag.default,
+            map: flag.map,
+            keys: flag.keys,
+        }
+    }
+
+    pub fn description(mut self, content: impl Into<String>) -> Self {
+        self.desc = content.into();
+        Self {
+            name: self.name,
+            short: self.short,
+            desc: self.desc,
+            env: self.env,
+            config: self.config,
+            counted: self.counted,
+            default: self.default,
+            map: self.map,
+            keys: self.keys,
+        }
+    }
+
+    pub fn short(mut self, short_name: impl Into<String>) -> Self {
+        self.short = short_name.into();
+        Self {
+            name: self.name,
+            short: self.short,
+            desc: self.desc,
+            env: self.env,
+            config: self.config,
+            counted: self.counted,
+            default: self.default,
+            map: self.map,
+            keys: self.keys,
+        }
+    }
+
+    /// Turns the flag on from environment variable `var` when it is not given on
+    /// the command line. Accepts `1`/`true`/`yes`/`on` and `0`/`false`/`no`/`off`
+    /// or empty.
+    pub fn env(mut self, var: impl Into<String>) -> Self {
+        self.env = var.into();
+        Self {
+            name: self.name,
+            short: self.short,
+            desc: self.desc,
+            env: self.env,
+            config: self.config,
+            counted: self.counted,
+            default: self.default,
+            map: self.map,
+            keys: self.keys,
+        }
+    }
+
+    /// Counts repeated occurrences, so `-vvv` gives a count of 3. The environment
+    /// and config files may also give the count as a number.
+    pub fn counted(mut self) -> Self {
+        self.counted = true;
+        Self {
+            name: self.name,
+            short: self.short,
+            desc: self.desc,
+            env: self.env,
+            config: self.config,
+            counted: self.counted,
+            default: self.default,
+            map: self.map,
+            keys: self.keys,
+        }
+    }
+
+    /// Whether the flag is on when nothing sets it. A flag that is on by default
+    /// can be turned off with `--no-<name>`.
+    pub fn default_value(mut self, on: bool) -> Self {
+        self.default = on;
+        Self {
+            name: self.name,
+            short: self.short,
+            desc: self.desc,
+            env: self.env,
+            config: self.config,
+            counted: self.counted,
+            default: self.default,
+            map: self.map,
+            keys: self.keys,
+        }
+    }
+
+    /// Takes a `key=value` pair, as in `--set tz=Asia/Bangkok`, and may be
+    /// repeated. The environment and config files give pairs separated by commas.
+    pub fn map(mut self) -> Self {
+        self.map = true;
+        Self {
+            name: self.name,
+            short: self.short,
+            desc: self.desc,
+            env: self.env,
+            config: self.config,
+            counted: self.counted,
+            default: self.default,
+            map: self.map,
+            keys: self.keys,
+        }
+    }
+
+    /// Validates the value of `key` in a map flag with `check`, which returns an
+    /// error message for a bad value. Keys without a check take any value.
+    ///
+    /// ```
+    /// use flex::{Command, Flag, Flex};
+    ///
+    /// let goal = |value: &str| value.parse::<u32>().map(|_| ()).map_err(|e| e.to_string());
+    /// let cmd = Command::default("config").flag(Flag::new("set").key("streak.goal", goal));
+    /// let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    ///
+    /// let matches = cmd.parse(&args(&["--set", "streak.goal=3", "--set=tz=Asia/Bangkok"])).unwrap();
+    /// assert_eq!(matches.map("set"), [("streak.goal", "3"), ("tz", "Asia/Bangkok")]);
+    /// assert!(cmd.parse(&args(&["--set", "streak.goal=many"])).is_err());
+    /// assert!(cmd.parse(&args(&["--set", "tz"])).is_err());
+    /// ```
+    pub fn key<F>(mut self, key: impl Into<String>, check: F) -> Self
+    where
+        F: Fn(&str) -> Result<(), String> + 'static,
+    {
+        self.map = true;
+        self.keys.push(KeyCheck {
+            key: key.into(),
+            check: Rc::new(check),
+        });
+        Self {
+            name: self.name,
+            short: self.short,
+            desc: self.desc,
+            env: self.env,
+            config: self.config,
+            counted: self.counted,
+            default: self.default,
+            map: self.map,
+            keys: self.keys,
+        }
+    }
+}
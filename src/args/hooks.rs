@@ -0,0 +1,187 @@
+use std::{cell::RefCell, error::Error, io::Write, rc::Rc};
+
+#[cfg(feature = "async")]
+use super::executor::{BoxFuture, CurrentThread, Executor};
+use super::{matches::Matches, state::State};
+use crate::output::Output;
+
+/// The command being run, as hooks and middleware see it.
+#[derive(Debug, Clone, Copy)]
+pub struct Invocation<'a> {
+    /// Names from the outermost command (the app, when run through one) down to
+    /// the command being run, e.g. `["flex", "repo", "add"]`.
+    pub path: &'a [String],
+    /// The arguments given to that command.
+    pub args: &'a [String],
+    /// `args` parsed against the command's declarations, for typed actions.
+    pub matches: Option<&'a Matches>,
+}
+
+pub type Outcome = Result<Output, Box<dyn Error>>;
+/// What a streaming action returns once it has written its output.
+pub type Streamed = Result<(), Box<dyn Error>>;
+pub type BeforeHook = Rc<dyn Fn(&Invocation) -> Result<(), Box<dyn Error>>>;
+pub type AfterHook = Rc<dyn Fn(&Invocation, &Outcome)>;
+/// Wraps running a command: call `next` to run it (and any inner middleware),
+/// or return without calling it to short-circuit.
+pub type Middleware = Rc<dyn Fn(&Invocation, &dyn Fn() -> Outcome) -> Outcome>;
+
+/// A command's hooks, which also run for every command below it.
+#[derive(Default, Clone)]
+pub struct Hooks {
+    pub before: Vec<BeforeHook>,
+    pub after: Vec<AfterHook>,
+}
+
+/// Hooks and middleware collected on the way down to the command being run.
+#[derive(Default, Clone)]
+pub(crate) struct RunContext<'a> {
+    path: Vec<String>,
+    before: Vec<&'a BeforeHook>,
+    after: Vec<&'a AfterHook>,
+    middleware: &'a [Middleware],
+    state: Option<&'a State>,
+    out: Option<&'a dyn Sink>,
+    no_input: bool,
+    #[cfg(feature = "async")]
+    executor: Option<&'a dyn Executor>,
+}
+
+impl<'a> RunContext<'a> {
+    pub fn new(app_name: &str, middleware: &'a [Middleware], state: &'a State) -> Self {
+        RunContext {
+            path: vec![app_name.to_string()],
+            middleware,
+            state: Some(state),
+            ..Default::default()
+        }
+    }
+
+    /// The names of the app and the commands entered so far, e.g. `flex repo add`.
+    pub fn command_path(&self) -> String {
+        self.path.join(" ")
+    }
+
+    /// The context inside command `name`, adding its hooks after its parents'.
+    pub fn enter(&self, name: &str, hooks: Option<&'a Hooks>) -> Self {
+        let mut ctx = self.clone();
+        ctx.path.push(name.to_string());
+        if let Some(hooks) = hooks {
+            ctx.before.extend(&hooks.before);
+            ctx.after.extend(&hooks.after);
+        }
+        ctx
+    }
+
+    /// Runs async actions on `executor` instead of [`CurrentThread`].
+    #[cfg(feature = "async")]
+    pub fn with_executor(mut self, executor: &'a dyn Executor) -> Self {
+        self.executor = Some(executor);
+        self
+    }
+
+    #[cfg(feature = "async")]
+    pub fn block_on(&self, future: BoxFuture) -> Outcome {
+        match self.executor {
+            Some(executor) => executor.block_on(future),
+            None => CurrentThread.block_on(future),
+        }
+    }
+
+    /// Lets streaming actions write to `out` as they go.
+    pub fn with_writer(mut self, out: &'a dyn Sink) -> Self {
+        self.out = Some(out);
+        self
+    }
+
+    /// Runs a streaming action against the context's writer. Without one the
+    /// output is collected and returned as text instead.
+    pub fn stream(&self, mut action: impl FnMut(&mut dyn Write) -> Streamed) -> Outcome {
+        match self.out {
+            Some(out) => {
+                out.write_with(&mut action)?;
+                Ok(Output::Empty)
+            }
+            None => {
+                let mut buffer = Vec::new();
+                action(&mut buffer)?;
+                let text = String::from_utf8_lossy(&buffer);
+                Ok(text.trim_end_matches('\n').to_string().into())
+            }
+        }
+    }
+
+    /// Never prompts for missing arguments, as `--no-input` asks.
+    pub fn without_input(mut self) -> Self {
+        self.no_input = true;
+        self
+    }
+
+    /// Whether missing arguments may be prompted for.
+    pub fn prompts(&self) -> bool {
+        !self.no_input
+    }
+
+    /// The app's shared state, if the command is run through an app.
+    pub fn state(&self) -> Option<&'a State> {
+        self.state
+    }
+
+    /// Passes `error` through the hooks and middleware in place of an action, so
+    /// they see a command that could not run like any other result.
+    pub fn fail<E: Error + Clone + 'static>(&self, args: &[String], error: E) -> Outcome {
+        self.run(args, None, &|| Err(Box::new(error.clone())))
+    }
+
+    /// Runs `action` inside the middleware, with before hooks from the outermost
+    /// command in and after hooks from the innermost command out. A failing
+    /// before hook skips the action; after hooks still see that error.
+    pub fn run(
+        &self,
+        args: &[String],
+        matches: Option<&Matches>,
+        action: &dyn Fn() -> Outcome,
+    ) -> Outcome {
+        let invocation = Invocation {
+            path: &self.path,
+            args,
+            matches,
+        };
+        let hooked = || {
+            let outcome = match self.before.iter().try_for_each(|hook| hook(&invocation)) {
+                Ok(()) => action(),
+                Err(e) => Err(e),
+            };
+            for hook in self.after.iter().rev() {
+                hook(&invocation, &outcome);
+            }
+            outcome
+        };
+        wrap(self.middleware, &invocation, &hooked)
+    }
+}
+
+/// A writer shared by every command run through an app. Behind a trait so
+/// [`RunContext`] stays covariant in its lifetime.
+pub(crate) trait Sink {
+    fn write_with(&self, action: &mut dyn FnMut(&mut dyn Write) -> Streamed) -> Streamed;
+}
+
+impl<W: Write> Sink for RefCell<W> {
+    fn write_with(&self, action: &mut dyn FnMut(&mut dyn Write) -> Streamed) -> Streamed {
+        let mut out = self.borrow_mut();
+        action(&mut *out)?;
+        Ok(out.flush()?)
+    }
+}
+
+fn wrap(
+    middleware: &[Middleware],
+    invocation: &Invocation,
+    inner: &dyn Fn() -> Outcome,
+) -> Outcome {
+    match middleware.split_first() {
+        None => inner(),
+        Some((outer, rest)) => outer(invocation, &|| wrap(rest, invocation, inner)),
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io::{self, Read},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use super::command::CommandError;
+
+/// Builds a value from a command's parsed arguments. Implement it by hand, or
+/// get it from `#[derive(Flex)]`.
+pub trait FromMatches: Sized {
+    fn from_matches(matches: &Matches) -> Result<Self, CommandError>;
+}
+
+impl FromMatches for Matches {
+    fn from_matches(matches: &Matches) -> Result<Self, CommandError> {
+        Ok(matches.clone())
+    }
+}
+
+/// Where a parsed value came from. The command line wins over the environment,
+/// then config files, then an argument's default, then an interactive prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueSource {
+    CommandLine,
+    Env(String),
+    Config(PathBuf),
+    Default,
+    Prompt,
+}
+
+impl Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueSource::CommandLine => write!(f, "command line"),
+            ValueSource::Env(var) => write!(f, "environment variable {}", var),
+            ValueSource::Config(path) => write!(f, "config file {}", path.display()),
+            ValueSource::Default => write!(f, "default"),
+            ValueSource::Prompt => write!(f, "prompt"),
+        }
+    }
+}
+
+/// Values collected by [`Command::parse`](super::command::Command::parse) for one
+/// command, with the matched subcommand (if any) nested inside.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Matches {
+    pub name: String,
+    pub values: Vec<(String, String)>,
+    pub flags: Vec<String>,
+    pub sources: Vec<(String, ValueSource)>,
+    pub subcommand: Option<Box<Matches>>,
+}
+
+impl Matches {
+    pub fn new(name: impl Into<String>) -> Self {
+        Matches {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(arg, _)| arg == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every value of argument `name`, in command-line order.
+    pub fn values_of(&self, name: &str) -> Vec<&str> {
+        self.values
+            .iter()
+            .filter(|(arg, _)| arg == name)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// The `key=value` pairs given to map flag `flag`, in the order keys first
+    /// appeared. A repeated key keeps its last value.
+    pub fn map(&self, flag: &str) -> Vec<(&str, &str)> {
+        let mut pairs: Vec<(&str, &str)> = Vec::new();
+        let given = self.values_of(flag);
+        for (key, value) in given.into_iter().filter_map(|pair| pair.split_once('=')) {
+            match pairs.iter_mut().find(|(seen, _)| *seen == key) {
+                Some(pair) => pair.1 = value,
+                None => pairs.push((key, value)),
+            }
+        }
+        pairs
+    }
+
+    pub fn is_present(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+
+    /// How many times `flag` was given, e.g. 3 for `-vvv`.
+    pub fn count(&self, flag: &str) -> usize {
+        self.flags.iter().filter(|f| *f == flag).count()
+    }
+
+    /// Whether argument `name` is the lone `-` that stands for standard input.
+    pub fn is_stdin(&self, name: &str) -> bool {
+        self.value(name) == Some("-")
+    }
+
+    /// Opens the file named by argument `name`, or standard input for `-`.
+    pub fn reader(&self, name: &str) -> Result<Option<Box<dyn Read>>, CommandError> {
+        match self.value(name) {
+            None => Ok(None),
+            Some("-") => Ok(Some(Box::new(io::stdin()))),
+            Some(path) => File::open(path)
+                .map(|file| Some(Box::new(file) as Box<dyn Read>))
+                .map_err(|e| {
+                    CommandError::InvalidArgument(format!(
+                        "Cannot open '{}' for <{}>: {}",
+                        path, name, e
+                    ))
+                }),
+        }
+    }
+
+    /// Where the value of argument or flag `name` came from, if it was set at all.
+    pub fn source(&self, name: &str) -> Option<&ValueSource> {
+        self.sources
+            .iter()
+            .find(|(arg, _)| arg == name)
+            .map(|(_, source)| source)
+    }
+
+    pub fn insert_value(&mut self, name: impl Into<String>, value: impl Into<String>, source: ValueSource) {
+        let name = name.into();
+        if self.source(&name).is_none() {
+            self.sources.push((name.clone(), source));
+        }
+        self.values.push((name, value.into()));
+    }
+
+    /// Records one occurrence of flag `name`.
+    pub fn insert_flag(&mut self, name: impl Into<String>, source: ValueSource) {
+        let name = name.into();
+        if self.source(&name).is_none() {
+            self.sources.push((name.clone(), source));
+        }
+        self.flags.push(name);
+    }
+
+    /// Turns flag `name` off, e.g. for `--no-<name>`, recording where that came from.
+    pub fn clear_flag(&mut self, name: impl Into<String>, source: ValueSource) {
+        let name = name.into();
+        self.flags.retain(|flag| *flag != name);
+        self.sources.retain(|(flag, _)| *flag != name);
+        self.sources.push((name, source));
+    }
+
+    pub fn subcommand(&self) -> Option<&Matches> {
+        self.subcommand.as_deref()
+    }
+
+    /// Parses the value of a required argument, failing if it is missing or
+    /// does not parse as `T`.
+    pub fn required<T>(&self, name: &str) -> Result<T, CommandError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.optional(name)?.ok_or_else(|| {
+            CommandError::InvalidArgument(format!(
+                "Command '{}' requires argument <{}>",
+                self.name, name
+            ))
+        })
+    }
+
+    /// Parses the value of an optional argument, failing only if it is present
+    /// but does not parse as `T`.
+    pub fn optional<T>(&self, name: &str) -> Result<Option<T>, CommandError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.value(name)
+            .map(|value| self.parse_value(name, value))
+            .transpose()
+    }
+
+    /// Parses every value of an argument that takes multiple values.
+    pub fn all<T>(&self, name: &str) -> Result<Vec<T>, CommandError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.values_of(name)
+            .into_iter()
+            .map(|value| self.parse_value(name, value))
+            .collect()
+    }
+
+    fn parse_value<T>(&self, name: &str, value: &str) -> Result<T, CommandError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        value.parse::<T>().map_err(|e| match self.source(name) {
+            Some(source @ (ValueSource::Env(_) | ValueSource::Config(_))) => {
+                CommandError::InvalidArgument(format!(
+                    "Invalid value '{}' in {} for <{}>: {}",
+                    value, source, name, e
+                ))
+            }
+            _ => CommandError::InvalidArgument(format!(
+                "Invalid value '{}' for <{}> in command '{}': {}",
+                value, name, self.name, e
+            )),
+        })
+    }
+}
@@ -0,0 +1,24 @@
+use std::env::args;
+
+use super::{
+    command::{Command, CommandError},
+    matches::FromMatches,
+};
+
+/// Implemented by `#[derive(Flex)]` to describe a type as a [`Command`] that
+/// parses back into it through [`FromMatches`].
+pub trait FlexParser: FromMatches {
+    fn command() -> Command;
+    /// Adds this type's arguments, flags and subcommands to `cmd`.
+    fn augment(cmd: Command) -> Command;
+
+    fn parse_from(args: &[String]) -> Result<Self, CommandError> {
+        let matches = Self::command().parse(args)?;
+        Self::from_matches(&matches)
+    }
+
+    fn parse() -> Result<Self, CommandError> {
+        let args: Vec<String> = args().skip(1).collect();
+        Self::parse_from(&args)
+    }
+}
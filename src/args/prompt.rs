@@ -0,0 +1,82 @@
+#[cfg(feature = "prompt")]
+use std::io::{self, BufRead, IsTerminal, Write};
+
+#[cfg(feature = "prompt")]
+use super::arg::Arg;
+
+/// How to ask for a missing required argument on an interactive terminal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Prompt {
+    Text,
+    /// Like `Text`, without echoing what is typed.
+    Password,
+    /// A y/n question, giving `true` or `false`.
+    Confirm,
+    /// One of the given values, picked by number or typed out.
+    Select(Vec<String>),
+}
+
+/// Whether prompts may be shown, i.e. standard input is a terminal.
+#[cfg(feature = "prompt")]
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal()
+}
+
+#[cfg(feature = "prompt")]
+impl Prompt {
+    /// Asks for a value for `arg` on standard error, asking again until the
+    /// answer is usable.
+    pub fn ask(&self, arg: &Arg) -> io::Result<String> {
+        let label = if arg.desc.is_empty() {
+            arg.name.clone()
+        } else {
+            format!("{} ({})", arg.name, arg.desc)
+        };
+        loop {
+            let answer = match self {
+                Prompt::Text => read_line(&format!("{}: ", label))?,
+                Prompt::Password => rpassword::prompt_password(format!("{}: ", label))?,
+                Prompt::Confirm => read_line(&format!("{} [y/n]: ", label))?,
+                Prompt::Select(choices) => {
+                    let mut stderr = io::stderr();
+                    for (i, choice) in choices.iter().enumerate() {
+                        writeln!(stderr, "  {}) {}", i + 1, choice)?;
+                    }
+                    read_line(&format!("{} [1-{}]: ", label, choices.len()))?
+                }
+            };
+            let answer = answer.trim();
+            let value = match self {
+                Prompt::Text | Prompt::Password => {
+                    Some(answer.to_string()).filter(|answer| !answer.is_empty())
+                }
+                Prompt::Confirm => match answer.to_lowercase().as_str() {
+                    "y" | "yes" => Some("true".to_string()),
+                    "n" | "no" => Some("false".to_string()),
+                    _ => None,
+                },
+                Prompt::Select(choices) => answer
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| choices.get(n.checked_sub(1)?))
+                    .or_else(|| choices.iter().find(|choice| *choice == answer))
+                    .cloned(),
+            };
+            if let Some(value) = value {
+                return Ok(value);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "prompt")]
+fn read_line(prompt: &str) -> io::Result<String> {
+    let mut stderr = io::stderr();
+    write!(stderr, "{}", prompt)?;
+    stderr.flush()?;
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no input"));
+    }
+    Ok(line)
+}
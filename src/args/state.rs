@@ -0,0 +1,37 @@
+use std::{
+    any::{Any, TypeId, type_name},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use super::command::CommandError;
+
+/// Values the app builder shares with every action, at most one per type, e.g.
+/// an open registry handle that several commands use.
+#[derive(Debug, Default, Clone)]
+pub struct State {
+    values: HashMap<TypeId, Rc<dyn Any>>,
+}
+
+impl State {
+    /// Stores `value`, replacing any earlier value of the same type.
+    pub fn insert<T: 'static>(&mut self, value: T) {
+        self.values.insert(TypeId::of::<T>(), Rc::new(value));
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+    }
+
+    /// Like [`State::get`], failing when the app never provided a `T`.
+    pub fn require<T: 'static>(&self) -> Result<&T, CommandError> {
+        self.get::<T>().ok_or_else(|| {
+            CommandError::InvalidConfiguration(format!(
+                "No shared state of type {} was given to the app",
+                type_name::<T>()
+            ))
+        })
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use toml::{Table, Value};
+
+use crate::{app::AppError, args::command::Command};
+
+/// One loaded config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub table: Table,
+}
+
+/// Config files layered in load order: a key in a later file overrides the same
+/// key in an earlier one.
+///
+/// Values are looked up by command path first and then at the top level, so for
+/// `repo add --all` both of these set the flag:
+///
+/// ```toml
+/// all = true
+///
+/// [repo.add]
+/// all = true
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    pub files: Vec<ConfigFile>,
+}
+
+impl Config {
+    /// Adds `path` as the highest-priority layer. A missing file is skipped.
+    pub fn load(mut self, path: impl AsRef<Path>) -> Result<Self, AppError> {
+        let path = path.as_ref();
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(self),
+            Err(e) => {
+                return Err(AppError::InvalidConfiguration(format!(
+                    "cannot read config file '{}': {}",
+                    path.display(),
+                    e
+                )));
+            }
+        };
+        let table = source.parse::<Table>().map_err(|e| {
+            AppError::InvalidConfiguration(format!("config file '{}': {}", path.display(), e))
+        })?;
+        self.files.push(ConfigFile {
+            path: path.to_path_buf(),
+            table,
+        });
+        Ok(self)
+    }
+
+    /// The user file (`$XDG_CONFIG_HOME/<app>/config.toml`, falling back to
+    /// `~/.config/<app>/config.toml`) followed by the project file `.<app>.toml`
+    /// in the current directory.
+    pub fn default_paths(app_name: &str) -> Vec<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+        let mut paths = Vec::new();
+        if let Some(config_home) = config_home {
+            paths.push(config_home.join(app_name).join("config.toml"));
+        }
+        paths.push(PathBuf::from(format!(".{}.toml", app_name)));
+        paths
+    }
+
+    /// Finds `key` for the command at `path`, returning the value and the file it
+    /// came from.
+    pub fn lookup(&self, path: &[String], key: &str) -> Option<(&Value, &Path)> {
+        fn scalar<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+            table.get(key).filter(|value| !value.is_table())
+        }
+        self.files.iter().rev().find_map(|file| {
+            path.iter()
+                .try_fold(&file.table, |table, name| match table.get(name) {
+                    Some(Value::Table(inner)) => Some(inner),
+                    _ => None,
+                })
+                .and_then(|table| scalar(table, key))
+                .or_else(|| scalar(&file.table, key))
+                .map(|value| (value, file.path.as_path()))
+        })
+    }
+
+    /// Records the config value of every argument and flag in `cmd`'s tree on the
+    /// declaration itself, so parsing can fall back to it after the command line
+    /// and the environment. `path` holds the names of `cmd`'s parent commands.
+    ///
+    /// An array gives one value per item to an argument taking multiple values
+    /// and a list of pairs to a map flag. Arrays for anything else are left out
+    /// and reported in the returned problems.
+    pub fn apply(&self, cmd: &mut Command, path: &mut Vec<String>) -> Vec<String> {
+        let mut problems = Vec::new();
+        let Command::Flex(cmd) = cmd else { return problems };
+        path.push(cmd.name.clone());
+        for arg in cmd.args.iter_mut() {
+            arg.config = match self.lookup(path, &arg.name) {
+                Some((Value::Array(items), file)) if arg.multiple => {
+                    Some((items.iter().map(scalar_value).collect(), file.to_path_buf()))
+                }
+                Some((Value::Array(_), file)) => {
+                    problems.push(array_problem(path, &arg.name, file, &format!("<{}>", arg.name)));
+                    None
+                }
+                Some((value, file)) => Some((vec![scalar_value(value)], file.to_path_buf())),
+                None => None,
+            };
+        }
+        for flag in cmd.flags.iter_mut() {
+            flag.config = match self.lookup(path, &flag.name) {
+                Some((Value::Array(items), file)) if flag.map => {
+                    let pairs: Vec<String> = items.iter().map(scalar_value).collect();
+                    Some((pairs.join(","), file.to_path_buf()))
+                }
+                Some((Value::Array(_), file)) => {
+                    problems.push(array_problem(path, &flag.name, file, &format!("--{}", flag.name)));
+                    None
+                }
+                Some((value, file)) => Some((scalar_value(value), file.to_path_buf())),
+                None => None,
+            };
+        }
+        for sub_cmd in cmd.sub_commands.iter_mut() {
+            problems.extend(self.apply(sub_cmd, path));
+        }
+        path.pop();
+        problems
+    }
+}
+
+fn scalar_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn array_problem(path: &[String], key: &str, file: &Path, target: &str) -> String {
+    format!(
+        "config file '{}': key '{}' for {} in '{}' must be a single value, not an array",
+        file.display(),
+        key,
+        target,
+        path.join(" ")
+    )
+}
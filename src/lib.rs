@@ -0,0 +1,38 @@
+//! Builder-style command line parsing behind the `flex` CLI: an [`App`] holds a
+//! tree of [`Command`]s, each declaring its [`Arg`]s, [`Flag`]s and action.
+
+extern crate self as flex;
+
+mod app;
+mod args;
+#[cfg(feature = "config")]
+mod config;
+mod output;
+mod pager;
+mod plugin;
+mod response_file;
+#[cfg(feature = "shell")]
+mod shell;
+#[cfg(feature = "spec")]
+mod spec;
+
+pub use app::{App, AppError, NamedAction};
+pub use output::{Output, OutputFormat};
+pub use args::{
+    arg::Arg,
+    command::{Action, Command, CommandError, CommandIndex, Flex, FlexCommand, HelpDocs},
+    flag::{Flag, KeyCheck, ValueCheck},
+    hooks::{AfterHook, BeforeHook, Hooks, Invocation, Middleware, Outcome, Streamed},
+    matches::{FromMatches, Matches, ValueSource},
+    parser::FlexParser,
+    prompt::Prompt,
+    state::State,
+};
+#[cfg(feature = "async")]
+pub use args::executor::{BoxFuture, CurrentThread, Executor};
+#[cfg(feature = "config")]
+pub use config::{Config, ConfigFile};
+#[cfg(feature = "derive")]
+pub use flex_derive::Flex;
+#[cfg(feature = "spec")]
+pub use spec::SpecFormat;
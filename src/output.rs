@@ -0,0 +1,286 @@
+use std::{fmt::Write, str::FromStr};
+
+/// What an action produces. Structured values are rendered by the app in the
+/// format picked with `--output`, so other tools can consume them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// Nothing to print, e.g. because the action already wrote its output.
+    Empty,
+    Text(String),
+    /// Key/value pairs describing one thing, in order.
+    Pairs(Vec<(String, String)>),
+    /// Rows of cells under named columns.
+    Table {
+        columns: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+}
+
+impl Output {
+    pub fn pairs<K, V>(pairs: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Output::Pairs(
+            pairs
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        )
+    }
+
+    pub fn table<C, R, V>(
+        columns: impl IntoIterator<Item = C>,
+        rows: impl IntoIterator<Item = R>,
+    ) -> Self
+    where
+        C: Into<String>,
+        R: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        Output::Table {
+            columns: columns.into_iter().map(Into::into).collect(),
+            rows: rows
+                .into_iter()
+                .map(|row| row.into_iter().map(Into::into).collect())
+                .collect(),
+        }
+    }
+
+    /// A table from records of key/value pairs. Columns follow the order keys
+    /// first appear in; a record without some key gets an empty cell.
+    pub fn records<K, V>(records: impl IntoIterator<Item = Vec<(K, V)>>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let records: Vec<Vec<(String, String)>> = records
+            .into_iter()
+            .map(|record| {
+                record
+                    .into_iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect()
+            })
+            .collect();
+        let mut columns: Vec<String> = Vec::new();
+        for (key, _) in records.iter().flatten() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+        let rows = records
+            .iter()
+            .map(|record| {
+                columns
+                    .iter()
+                    .map(|column| {
+                        record
+                            .iter()
+                            .find(|(key, _)| key == column)
+                            .map(|(_, value)| value.clone())
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            })
+            .collect();
+        Output::Table { columns, rows }
+    }
+
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Text => self.text(),
+            OutputFormat::Json => self.json(),
+            OutputFormat::Yaml => self.yaml(),
+            OutputFormat::Csv => self.csv(),
+        }
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Output::Empty => String::new(),
+            Output::Text(text) => text.clone(),
+            Output::Pairs(pairs) => {
+                let width = pairs.iter().map(|(key, _)| key.chars().count() + 1).max();
+                pairs
+                    .iter()
+                    .map(|(key, value)| {
+                        let key = format!("{}:", key);
+                        format!("{:<width$} {}", key, value, width = width.unwrap_or(0))
+                            .trim_end()
+                            .to_string()
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Output::Table { columns, rows } => {
+                let mut widths: Vec<usize> = columns.iter().map(|c| c.chars().count()).collect();
+                for row in rows {
+                    for (width, cell) in widths.iter_mut().zip(row) {
+                        *width = (*width).max(cell.chars().count());
+                    }
+                }
+                std::iter::once(columns)
+                    .chain(rows)
+                    .map(|row| {
+                        row.iter()
+                            .zip(&widths)
+                            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                            .collect::<Vec<_>>()
+                            .join("  ")
+                            .trim_end()
+                            .to_string()
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        }
+    }
+
+    fn json(&self) -> String {
+        let object = |pairs: &mut dyn Iterator<Item = (&String, &String)>| {
+            let fields: Vec<String> = pairs
+                .map(|(key, value)| format!("{}: {}", quote(key), quote(value)))
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        };
+        match self {
+            Output::Empty => String::new(),
+            Output::Text(text) => quote(text),
+            Output::Pairs(pairs) => object(&mut pairs.iter().map(|(key, value)| (key, value))),
+            Output::Table { columns, rows } => {
+                let rows: Vec<String> = rows
+                    .iter()
+                    .map(|row| object(&mut columns.iter().zip(row)))
+                    .collect();
+                format!("[{}]", rows.join(", "))
+            }
+        }
+    }
+
+    fn yaml(&self) -> String {
+        // Double-quoted scalars use JSON escapes, which YAML accepts as-is.
+        match self {
+            Output::Empty => String::new(),
+            Output::Text(text) => quote(text),
+            Output::Pairs(pairs) if pairs.is_empty() => "{}".to_string(),
+            Output::Pairs(pairs) => pairs
+                .iter()
+                .map(|(key, value)| format!("{}: {}", quote(key), quote(value)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Output::Table { rows, .. } if rows.is_empty() => "[]".to_string(),
+            Output::Table { columns, rows } => {
+                let mut output = String::new();
+                for row in rows {
+                    for (i, (column, cell)) in columns.iter().zip(row).enumerate() {
+                        let dash = if i == 0 { "- " } else { "  " };
+                        let _ = writeln!(output, "{}{}: {}", dash, quote(column), quote(cell));
+                    }
+                }
+                output.trim_end().to_string()
+            }
+        }
+    }
+
+    fn csv(&self) -> String {
+        let line = |cells: &mut dyn Iterator<Item = &String>| {
+            cells
+                .map(|cell| csv_field(cell))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        match self {
+            Output::Empty => String::new(),
+            Output::Text(text) => text.clone(),
+            Output::Pairs(pairs) => std::iter::once("key,value".to_string())
+                .chain(
+                    pairs
+                        .iter()
+                        .map(|(key, value)| line(&mut [key, value].into_iter())),
+                )
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Output::Table { columns, rows } => std::iter::once(line(&mut columns.iter()))
+                .chain(rows.iter().map(|row| line(&mut row.iter())))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+impl From<()> for Output {
+    fn from(_: ()) -> Self {
+        Output::Empty
+    }
+}
+
+impl From<String> for Output {
+    fn from(text: String) -> Self {
+        Output::Text(text)
+    }
+}
+
+impl From<&str> for Output {
+    fn from(text: &str) -> Self {
+        Output::Text(text.to_string())
+    }
+}
+
+/// How `--output` renders an [`Output`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Plain text with aligned tables.
+    #[default]
+    Text,
+    Json,
+    Yaml,
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!(
+                "unknown output format '{}': expected text, json, yaml or csv",
+                name
+            )),
+        }
+    }
+}
+
+/// `text` as a JSON string literal.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// `cell` as a CSV field, quoted when it contains a separator, quote or newline.
+fn csv_field(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
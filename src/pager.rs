@@ -0,0 +1,51 @@
+#[cfg(feature = "pager")]
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    process::{Command, Stdio},
+};
+
+#[cfg(feature = "pager")]
+use terminal_size::{Height, terminal_size};
+
+/// Shows `text` through the user's pager when standard output is a terminal
+/// too short to hold it. `var` (e.g. `FLEX_PAGER`) overrides `$PAGER`, and
+/// either set to an empty value turns paging off. Returns whether the text was
+/// paged; when it was not the caller prints it as usual.
+#[cfg(feature = "pager")]
+pub fn page(text: &str, var: &str) -> bool {
+    if !io::stdout().is_terminal() {
+        return false;
+    }
+    let Some((_, Height(height))) = terminal_size() else {
+        return false;
+    };
+    if text.lines().count() < height as usize {
+        return false;
+    }
+    let pager = env::var(var)
+        .or_else(|_| env::var("PAGER"))
+        .unwrap_or_else(|_| "less -R".to_string());
+    let mut words = pager.split_whitespace();
+    let Some(program) = words.next() else {
+        return false;
+    };
+    let Ok(mut child) = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()
+    else {
+        return false;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // Quitting the pager early closes the pipe; that is not an error.
+        let _ = writeln!(stdin, "{}", text);
+    }
+    let _ = child.wait();
+    true
+}
+
+#[cfg(not(feature = "pager"))]
+pub fn page(_text: &str, _var: &str) -> bool {
+    false
+}
@@ -0,0 +1,70 @@
+use std::{
+    env::{self, consts::EXE_SUFFIX},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{app::AppError, args::hooks::Outcome, output::Output};
+
+/// The `<app>-<name>` executable on `PATH` that provides command `name`, if
+/// any. The first directory that has one wins, as with any other program.
+pub fn find(app_name: &str, name: &str) -> Option<PathBuf> {
+    if name.is_empty() || name.starts_with('-') || name.contains(['/', '\\']) {
+        return None;
+    }
+    let file_name = format!("{}-{}{}", app_name, name, EXE_SUFFIX);
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| is_executable(path))
+}
+
+/// Names of the plugins on `PATH`, e.g. `foo` for `flex-foo`, sorted and
+/// without duplicates.
+pub fn discover(app_name: &str) -> Vec<String> {
+    let prefix = format!("{}-", app_name);
+    let mut names: Vec<String> = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| is_executable(&entry.path()))
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            let name = file_name.strip_prefix(&prefix)?.strip_suffix(EXE_SUFFIX)?;
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Runs the plugin at `path` with `args`, sharing the terminal. Its output goes
+/// straight to standard output, so there is nothing to return on success; a
+/// non-zero exit becomes [`AppError::PluginExit`] with the same code.
+pub fn run(path: &Path, args: &[String]) -> Outcome {
+    let status = Command::new(path).args(args).status().map_err(|e| {
+        AppError::InvalidCommand(format!("cannot run plugin {}: {}", path.display(), e))
+    })?;
+    if status.success() {
+        Ok(Output::Empty)
+    } else {
+        // Killed by a signal: there is no code to pass on.
+        Err(Box::new(AppError::PluginExit(status.code().unwrap_or(1))))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,116 @@
+use std::{fs, path::Path};
+
+use crate::app::AppError;
+
+/// Replaces each `@path` argument with the arguments read from that file, see
+/// [`App::response_files`](crate::App::response_files) for the syntax.
+pub fn expand(args: Vec<String>, max_depth: usize) -> Result<Vec<String>, AppError> {
+    expand_in(args, Path::new(""), 0, max_depth)
+}
+
+/// Expands `args` read `depth` files deep, naming nested paths relative to `base`.
+fn expand_in(args: Vec<String>, base: &Path, depth: usize, max_depth: usize) -> Result<Vec<String>, AppError> {
+    let mut expanded = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            expanded.push(arg);
+            expanded.extend(args.by_ref());
+        } else if let Some(literal) = arg.strip_prefix("@@") {
+            expanded.push(format!("@{}", literal));
+        } else if let Some(path) = arg.strip_prefix('@').filter(|path| !path.is_empty()) {
+            let path = base.join(path);
+            if depth > max_depth {
+                return Err(AppError::ResponseFile(format!(
+                    "'{}' is nested more than {} files deep (is a file including itself?)",
+                    path.display(),
+                    max_depth
+                )));
+            }
+            let words = read(&path)?;
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            expanded.extend(expand_in(words, &dir, depth + 1, max_depth)?);
+        } else {
+            expanded.push(arg);
+        }
+    }
+    Ok(expanded)
+}
+
+fn read(path: &Path) -> Result<Vec<String>, AppError> {
+    let source = fs::read_to_string(path).map_err(|e| {
+        AppError::ResponseFile(format!("cannot read '{}': {}", path.display(), e))
+    })?;
+    let mut words = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        let line_words = split_words(line).map_err(|e| {
+            AppError::ResponseFile(format!("{}:{}: {}", path.display(), i + 1, e))
+        })?;
+        words.extend(line_words);
+    }
+    Ok(words)
+}
+
+/// Splits `line` into words the way a POSIX shell would, without expansions.
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err("trailing backslash".to_string()),
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
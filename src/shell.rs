@@ -0,0 +1,146 @@
+use std::{env, error::Error, fs, path::PathBuf};
+
+use rustyline::{
+    Context, Editor, Helper, completion::Completer, error::ReadlineError, highlight::Highlighter,
+    hint::Hinter, history::DefaultHistory, validate::Validator,
+};
+
+use crate::{app::App, args::command::Command, response_file::split_words};
+
+/// `$XDG_STATE_HOME/<app>/history`, falling back to
+/// `~/.local/state/<app>/history`.
+pub fn default_history_file(app_name: &str) -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .map(|state_home| state_home.join(app_name).join("history"))
+}
+
+/// Reads lines until `exit` or end of input and runs each one through `app` as
+/// if it were a command line, printing the output or the error.
+pub fn run(app: &App) -> Result<String, Box<dyn Error>> {
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper { app }));
+    if let Some(path) = &app.history_file {
+        // A missing history file just means a first session.
+        let _ = editor.load_history(path);
+    }
+
+    let prompt = format!("{}> ", app.name);
+    loop {
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let words = match split_words(&line) {
+            Ok(words) => words,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                continue;
+            }
+        };
+        let Some(first) = words.first() else { continue };
+        editor.add_history_entry(line.as_str())?;
+        match first.as_str() {
+            "exit" | "quit" => break,
+            "shell" => eprintln!("Error: already in the {} shell", app.name),
+            "help" if app.find_command("help").is_none() => println!("{}", app.render_help()),
+            _ => match app.run_from(words) {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => println!("{}", output),
+                Err(e) => eprintln!("Error: {}", e),
+            },
+        }
+    }
+
+    if let Some(path) = &app.history_file {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        editor.save_history(path)?;
+    }
+    Ok(String::new())
+}
+
+/// Tab completion of command names and flags from the app's command tree.
+struct ShellHelper<'a> {
+    app: &'a App,
+}
+
+impl ShellHelper<'_> {
+    /// Completions for `partial` after the complete `words` before it.
+    fn candidates(&self, words: &[String], partial: &str) -> Vec<String> {
+        let mut commands: &[Command] = &self.app.commands;
+        let mut current: Option<&Command> = None;
+        for word in words.iter().filter(|word| !word.starts_with('-')) {
+            let found = match current {
+                None => self.app.find_command(word),
+                Some(cmd) => cmd.find_subcommand(word),
+            };
+            match found {
+                Some(cmd) => {
+                    commands = cmd.get_available_cmds();
+                    current = Some(cmd);
+                }
+                // A positional value; subcommands only come before those.
+                None => commands = &[],
+            }
+        }
+
+        let mut candidates: Vec<String> = if partial.starts_with('-') {
+            current
+                .map(Command::get_flags)
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|flag| {
+                    let negated = flag.default.then(|| format!("--no-{}", flag.name));
+                    std::iter::once(format!("--{}", flag.name)).chain(negated)
+                })
+                .collect()
+        } else {
+            commands
+                .iter()
+                .map(|cmd| cmd.get_cmd_name().to_string())
+                .filter(|name| current.is_some() || name != "shell")
+                .collect()
+        };
+        if words.is_empty() {
+            candidates.extend(["exit".to_string(), "help".to_string()]);
+        }
+        candidates.retain(|candidate| candidate.starts_with(partial));
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+}
+
+impl Completer for ShellHelper<'_> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let candidates = match split_words(&line[..start]) {
+            Ok(words) => self.candidates(&words, &line[start..]),
+            Err(_) => Vec::new(),
+        };
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper<'_> {}
+
+impl Validator for ShellHelper<'_> {}
+
+impl Helper for ShellHelper<'_> {}
@@ -0,0 +1,312 @@
+use std::{fmt::Display, path::Path};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    app::{App, AppError},
+    args::{
+        arg::Arg,
+        command::{Action, Command, Flex, FlexCommand, HelpDocs},
+        flag::Flag,
+        prompt::Prompt,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecFormat {
+    Toml,
+    Json,
+}
+
+impl SpecFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(SpecFormat::Toml),
+            "json" => Some(SpecFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(key: &str, reason: impl Display) -> AppError {
+    AppError::InvalidConfiguration(format!("spec key `{}`: {}", key, reason))
+}
+
+/// Reads either format into a JSON value, so both share the same walker and
+/// error paths.
+pub fn parse(source: &str, format: SpecFormat) -> Result<Value, AppError> {
+    let parse_error = |e: &dyn Display| AppError::InvalidConfiguration(format!("spec: {}", e));
+    match format {
+        SpecFormat::Json => serde_json::from_str(source).map_err(|e| parse_error(&e)),
+        SpecFormat::Toml => {
+            let value: toml::Value = toml::from_str(source).map_err(|e| parse_error(&e))?;
+            serde_json::to_value(value).map_err(|e| parse_error(&e))
+        }
+    }
+}
+
+/// One spec table, remembering where it sits so errors can name the full key.
+struct Table<'a> {
+    key: String,
+    entries: &'a Map<String, Value>,
+}
+
+impl<'a> Table<'a> {
+    fn new(key: impl Into<String>, value: &'a Value) -> Result<Self, AppError> {
+        let key = key.into();
+        match value {
+            Value::Object(entries) => Ok(Table { key, entries }),
+            _ => Err(invalid(&key, "expected a table")),
+        }
+    }
+
+    fn key_of(&self, name: &str) -> String {
+        if self.key.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", self.key, name)
+        }
+    }
+
+    fn allow(&self, names: &[&str]) -> Result<(), AppError> {
+        match self.entries.keys().find(|name| !names.contains(&name.as_str())) {
+            Some(name) => Err(invalid(&self.key_of(name), "unknown key")),
+            None => Ok(()),
+        }
+    }
+
+    fn string(&self, name: &str) -> Result<Option<String>, AppError> {
+        match self.entries.get(name) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(invalid(&self.key_of(name), "expected a string")),
+        }
+    }
+
+    fn required_string(&self, name: &str) -> Result<String, AppError> {
+        self.string(name)?
+            .ok_or_else(|| invalid(&self.key_of(name), "missing required key"))
+    }
+
+    fn bool(&self, name: &str) -> Result<bool, AppError> {
+        match self.entries.get(name) {
+            None => Ok(false),
+            Some(Value::Bool(value)) => Ok(*value),
+            Some(_) => Err(invalid(&self.key_of(name), "expected true or false")),
+        }
+    }
+
+    fn count(&self, name: &str) -> Result<Option<usize>, AppError> {
+        match self.entries.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .as_u64()
+                .map(|count| Some(count as usize))
+                .ok_or_else(|| invalid(&self.key_of(name), "expected a non-negative integer")),
+        }
+    }
+
+    fn strings(&self, name: &str) -> Result<Vec<String>, AppError> {
+        match self.entries.get(name) {
+            None => Ok(Vec::new()),
+            Some(Value::Array(values)) => values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| match value {
+                    Value::String(value) => Ok(value.clone()),
+                    _ => Err(invalid(
+                        &format!("{}[{}]", self.key_of(name), i),
+                        "expected a string",
+                    )),
+                })
+                .collect(),
+            Some(_) => Err(invalid(&self.key_of(name), "expected an array of strings")),
+        }
+    }
+
+    fn tables(&self, name: &str) -> Result<Vec<Table<'a>>, AppError> {
+        match self.entries.get(name) {
+            None => Ok(Vec::new()),
+            Some(Value::Array(values)) => values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| Table::new(format!("{}[{}]", self.key_of(name), i), value))
+                .collect(),
+            Some(_) => Err(invalid(&self.key_of(name), "expected an array of tables")),
+        }
+    }
+}
+
+pub fn apply(mut app: App, spec: &Value) -> Result<App, AppError> {
+    let root = Table::new("", spec)?;
+    root.allow(&["name", "about", "env_prefix", "help", "default_command", "commands"])?;
+    if let Some(name) = root.string("name")? {
+        app.name = name;
+    }
+    if let Some(about) = root.string("about")? {
+        app.about = about;
+    }
+    if let Some(prefix) = root.string("env_prefix")? {
+        app = app.env_prefix(prefix);
+    }
+    for table in root.tables("commands")? {
+        let cmd = command(&app, &table)?;
+        app = app.add_command(cmd);
+    }
+    if root.bool("help")? {
+        app = app.help();
+    }
+    if let Some(name) = root.string("default_command")? {
+        app = app.default_command(name);
+    }
+    Ok(app)
+}
+
+fn command(app: &App, table: &Table) -> Result<Command, AppError> {
+    table.allow(&[
+        "name",
+        "description",
+        "long_about",
+        "examples",
+        "after_help",
+        "category",
+        "aliases",
+        "action",
+        "help",
+        "args",
+        "flags",
+        "subcommands",
+        "default_subcommand",
+    ])?;
+    let action = match table.string("action")? {
+        Some(name) => {
+            let action = app.get_action(&name).ok_or_else(|| {
+                invalid(
+                    &table.key_of("action"),
+                    format!("no action named '{}' is registered", name),
+                )
+            })?;
+            Some(Action::Args(Box::new(move |args: &[String]| action(args))))
+        }
+        None => None,
+    };
+    let mut cmd = Command::flex(FlexCommand {
+        name: table.required_string("name")?,
+        desc: table.string("description")?.unwrap_or_default(),
+        docs: Box::new(HelpDocs {
+            long_about: table.string("long_about")?.unwrap_or_default(),
+            after_help: table.string("after_help")?.unwrap_or_default(),
+            category: table.string("category")?.unwrap_or_default(),
+            ..Default::default()
+        }),
+        aliases: table.strings("aliases")?,
+        default_subcommand: table.string("default_subcommand")?,
+        action,
+        ..Default::default()
+    });
+
+    for example_table in table.tables("examples")? {
+        example_table.allow(&["command", "description"])?;
+        cmd = cmd.example(
+            example_table.required_string("command")?,
+            example_table.string("description")?.unwrap_or_default(),
+        );
+    }
+
+    for arg_table in table.tables("args")? {
+        arg_table.allow(&[
+            "name",
+            "description",
+            "required",
+            "env",
+            "default",
+            "multiple",
+            "min",
+            "max",
+            "rest",
+            "prompt",
+            "choices",
+        ])?;
+        let mut arg = Arg::new(arg_table.required_string("name")?)
+            .description(arg_table.string("description")?.unwrap_or_default());
+        if arg_table.bool("required")? {
+            arg = arg.required();
+        }
+        if let Some(env) = arg_table.string("env")? {
+            arg = arg.env(env);
+        }
+        if let Some(default) = arg_table.string("default")? {
+            arg = arg.default_value(default);
+        }
+        if arg_table.bool("multiple")? {
+            arg = arg.multiple();
+        }
+        if let Some(min) = arg_table.count("min")? {
+            arg = arg.min_values(min);
+        }
+        if let Some(max) = arg_table.count("max")? {
+            arg = arg.max_values(max);
+        }
+        if arg_table.bool("rest")? {
+            arg = arg.rest();
+        }
+        let choices = arg_table.strings("choices")?;
+        match arg_table.string("prompt")?.as_deref() {
+            None if choices.is_empty() => {}
+            None | Some("select") => arg = arg.prompt(Prompt::Select(choices)),
+            Some("text") => arg = arg.prompt(Prompt::Text),
+            Some("password") => arg = arg.prompt(Prompt::Password),
+            Some("confirm") => arg = arg.prompt(Prompt::Confirm),
+            Some(_) => {
+                return Err(invalid(
+                    &arg_table.key_of("prompt"),
+                    "expected text, password, confirm or select",
+                ));
+            }
+        }
+        cmd = cmd.arg(arg);
+    }
+
+    for flag_table in table.tables("flags")? {
+        flag_table.allow(&[
+            "name",
+            "short",
+            "description",
+            "env",
+            "counted",
+            "default",
+            "map",
+        ])?;
+        let mut flag = Flag::new(flag_table.required_string("name")?)
+            .description(flag_table.string("description")?.unwrap_or_default());
+        if let Some(env) = flag_table.string("env")? {
+            flag = flag.env(env);
+        }
+        if let Some(short) = flag_table.string("short")? {
+            if short.chars().count() != 1 {
+                return Err(invalid(
+                    &flag_table.key_of("short"),
+                    "expected a single character",
+                ));
+            }
+            flag = flag.short(short);
+        }
+        if flag_table.bool("counted")? {
+            flag = flag.counted();
+        }
+        if flag_table.bool("map")? {
+            flag = flag.map();
+        }
+        flag = flag.default_value(flag_table.bool("default")?);
+        cmd = cmd.flag(flag);
+    }
+
+    for sub_table in table.tables("subcommands")? {
+        cmd = cmd.subcommand(command(app, &sub_table)?);
+    }
+
+    if table.bool("help")? {
+        cmd = cmd.help();
+    }
+    Ok(cmd)
+}
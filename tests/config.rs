@@ -0,0 +1,66 @@
+#![cfg(feature = "config")]
+
+use std::{fs, path::PathBuf};
+
+use flex::{App, Arg, Command, Flag, Flex};
+
+fn config_file(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("flex-test-{}-{}.toml", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn paths_command() -> Command {
+    Command::default("add")
+        .arg(Arg::new("paths").multiple())
+        .flag(Flag::new("label").map())
+}
+
+#[test]
+fn array_gives_multiple_values() {
+    let path = config_file("array", "[add]\npaths = [\"a\", \"b c\"]\nlabel = [\"x=1\", \"y=2\"]\n");
+    let app = App::new("flex").add_command(paths_command()).config_file(&path).unwrap();
+    let matches = app.find_command("add").unwrap().parse(&[]).unwrap();
+    assert_eq!(matches.values_of("paths"), ["a", "b c"]);
+    assert_eq!(matches.map("label"), [("x", "1"), ("y", "2")]);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn array_for_single_value_names_the_key() {
+    let path = config_file("single", "[add]\nname = [\"a\", \"b\"]\n");
+    let err = App::new("flex")
+        .add_command(Command::default("add").arg(Arg::new("name")))
+        .config_file(&path)
+        .unwrap_err();
+    assert!(err.to_string().contains("key 'name'"), "{}", err);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn show_config_sources_is_an_app_switch() {
+    let app = App::new("flex")
+        .add_command(Command::default("exec").action(|args| Ok::<_, flex::CommandError>(args.join(" "))))
+        .add_command(
+            Command::default("run")
+                .arg(Arg::new("command").rest())
+                .typed_action(|m: flex::Matches| Ok(m.values_of("command").join(" "))),
+        );
+    let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    assert_eq!(app.run_from(args(&["exec", "grep", "--show-config-sources"])).unwrap(), "grep --show-config-sources");
+    assert_eq!(app.run_from(args(&["run", "git", "--show-config-sources"])).unwrap(), "git --show-config-sources");
+    let report = app.run_from(args(&["--show-config-sources", "run", "git"])).unwrap();
+    assert!(report.contains("<command> = git"), "{}", report);
+}
+
+#[test]
+fn array_is_checked_against_the_value_count() {
+    let path = config_file("count", "[add]\npaths = [\"a\", \"b\", \"c\"]\n");
+    let app = App::new("flex")
+        .add_command(Command::default("add").arg(Arg::new("paths").max_values(2)))
+        .config_file(&path)
+        .unwrap();
+    let err = app.find_command("add").unwrap().parse(&[]).unwrap_err().to_string();
+    assert!(err.contains("takes at most 2 values for <paths>, got 3 from the config file"), "{}", err);
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,34 @@
+use flex::{App, Command, Flag, Flex, Matches, Output, State};
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn app() -> App {
+    let list = Command::default("list").flag(Flag::new("extended").short("x")).output_action(
+        |_: &State, m: Matches| {
+            let kind = if m.is_present("extended") { "extended" } else { "short" };
+            Ok(Output::table(["kind"], [[kind]]))
+        },
+    );
+    App::new("flex")
+        .add_command(Command::default("repo").subcommand(list).default_subcommand("list"))
+        .default_command("repo")
+}
+
+#[test]
+fn bare_app_runs_the_default_command() {
+    assert_eq!(app().run_from(args(&[])).unwrap(), "kind\nshort");
+}
+
+#[test]
+fn flags_alone_go_to_the_default_command() {
+    assert_eq!(app().run_from(args(&["-x"])).unwrap(), "kind\nextended");
+}
+
+#[test]
+fn default_subcommand_takes_the_output_flag() {
+    let output = app().run_from(args(&["repo", "--output", "json"])).unwrap();
+    assert!(output.starts_with("["), "{}", output);
+    assert!(output.contains("\"short\""), "{}", output);
+}
@@ -0,0 +1,17 @@
+#![cfg(feature = "derive")]
+
+use flex::{Flex, FlexParser};
+
+#[derive(Flex, Debug)]
+struct Opt {
+    /// how many to show
+    #[flex(default = "3")]
+    n: Option<u32>,
+}
+
+#[test]
+fn default_applies_to_option_field() {
+    assert_eq!(Opt::parse_from(&[]).unwrap().n, Some(3));
+    assert_eq!(Opt::parse_from(&["7".to_string()]).unwrap().n, Some(7));
+    assert!(Opt::command().render_help("opt").contains("[default: 3]"));
+}
@@ -0,0 +1,29 @@
+use flex::{Arg, Command, Flex};
+
+// One test only: `set_var` must not race with anything else reading the
+// environment, and tests in one binary run on parallel threads.
+#[test]
+fn env_values() {
+    // SAFETY: this is the only test in this binary, so nothing else touches the
+    // environment concurrently.
+    unsafe {
+        std::env::set_var("FLEX_TEST_ENV_PATHS", "a 'b c' d");
+        std::env::set_var("FLEX_TEST_ENV_NAME", "a b");
+        std::env::set_var("FLEX_TEST_ENV_MAX", "a b c");
+    }
+
+    let cmd = Command::default("add").arg(Arg::new("paths").multiple().env("FLEX_TEST_ENV_PATHS"));
+    let matches = cmd.parse(&[]).unwrap();
+    assert_eq!(matches.values_of("paths"), ["a", "b c", "d"], "multiple values split into words");
+
+    let cmd = Command::default("add").arg(Arg::new("name").env("FLEX_TEST_ENV_NAME"));
+    let matches = cmd.parse(&[]).unwrap();
+    assert_eq!(matches.value("name"), Some("a b"), "a single value stays whole");
+
+    let cmd = Command::default("add").arg(Arg::new("p").max_values(2).env("FLEX_TEST_ENV_MAX"));
+    assert_eq!(
+        cmd.parse(&[]).unwrap_err().to_string(),
+        "Invalid argument: Command 'add' takes at most 2 values for <p>, got 3 from the environment variable FLEX_TEST_ENV_MAX",
+        "the value count is checked after splitting"
+    );
+}
@@ -0,0 +1,40 @@
+use flex::{App, Arg, Command, Flag, Flex};
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn app() -> App {
+    let add = Command::default("add")
+        .description("Add a repository")
+        .arg(Arg::new("path").required())
+        .flag(Flag::new("fetch").default_value(true).description("Fetch after adding"))
+        .flag(Flag::new("verbose").short("v").description("Say more"))
+        .typed_action(|_: flex::Matches| Ok(String::new()));
+    App::new("tool").add_command(Command::default("repo").subcommand(add).help())
+}
+
+#[test]
+fn usage_names_the_app_and_command_path() {
+    let app = app();
+    let help = app.run_with_output(args(&["repo", "add", "--help"]), &mut Vec::new()).unwrap();
+    assert!(help.starts_with("Usage: tool repo add [<flags>] <path>"), "{}", help);
+    let help = app.run_with_output(args(&["repo", "help"]), &mut Vec::new()).unwrap();
+    assert!(help.starts_with("Usage: tool repo "), "{}", help);
+}
+
+#[test]
+fn flag_without_short_name_leaves_the_column_blank() {
+    let help = app().run_with_output(args(&["repo", "add", "--help"]), &mut Vec::new()).unwrap();
+    assert!(help.contains("\n  --[no-]fetch              Fetch after adding\n"), "{}", help);
+    assert!(help.contains("\n  --verbose        -v       Say more"), "{}", help);
+}
+
+#[test]
+fn leaf_command_has_no_subcommand_section() {
+    let app = app();
+    let help = app.run_with_output(args(&["repo", "add", "--help"]), &mut Vec::new()).unwrap();
+    assert!(!help.contains("Subcommands"), "{}", help);
+    let help = app.run_with_output(args(&["repo", "help"]), &mut Vec::new()).unwrap();
+    assert!(help.contains("Available Subcommands:\n  add "), "{}", help);
+}
@@ -0,0 +1,58 @@
+use std::{cell::RefCell, rc::Rc};
+
+use flex::{App, Arg, Command, Flex, Matches};
+
+#[test]
+fn parse_errors_run_through_hooks_and_middleware() {
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let (before, after, around) = (seen.clone(), seen.clone(), seen.clone());
+    let app = App::new("flex")
+        .middleware(move |_, next| {
+            around.borrow_mut().push("middleware");
+            next()
+        })
+        .add_command(
+            Command::default("add")
+                .arg(Arg::new("count").required())
+                .typed_action(|m: Matches| m.required::<u32>("count").map(|n| n.to_string()).map_err(Into::into))
+                .before(move |_| {
+                    before.borrow_mut().push("before");
+                    Ok(())
+                })
+                .after(move |_, outcome| after.borrow_mut().push(if outcome.is_err() { "error" } else { "ok" })),
+        );
+    assert!(app.run_from(vec!["add".to_string()]).is_err());
+    assert_eq!(*seen.borrow(), ["middleware", "before", "error"]);
+}
+
+#[test]
+fn unknown_and_missing_commands_run_through_hooks_and_middleware() {
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let (after, around) = (seen.clone(), seen.clone());
+    let app = App::new("flex")
+        .middleware(move |invocation, next| {
+            let outcome = next();
+            around.borrow_mut().push(format!("middleware {} {}", invocation.path.join(" "), outcome.is_err()));
+            outcome
+        })
+        .add_command(
+            Command::default("repo")
+                .subcommand(Command::default("list"))
+                .after(move |invocation, outcome| {
+                    after.borrow_mut().push(format!("after {} {}", invocation.path.join(" "), outcome.is_err()))
+                }),
+        );
+    assert!(app.run_from(vec!["repo".to_string(), "bogus".to_string()]).is_err());
+    assert!(app.run_from(vec!["repo".to_string()]).is_err());
+    assert!(app.run_from(vec!["bogus".to_string()]).is_err());
+    assert_eq!(
+        *seen.borrow(),
+        [
+            "after flex repo true",
+            "middleware flex repo true",
+            "after flex repo true",
+            "middleware flex repo true",
+            "middleware flex true",
+        ]
+    );
+}
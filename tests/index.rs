@@ -0,0 +1,45 @@
+use flex::{App, Command, Flag, Flex};
+
+#[test]
+fn stale_app_index_falls_back_to_search() {
+    let mut app = App::new("flex")
+        .add_command(Command::default("one"))
+        .add_command(Command::default("two"));
+    assert!(app.find_command("two").is_some());
+    app.commands.remove(0);
+    assert_eq!(app.find_command("two").map(Command::get_cmd_name), Some("two"));
+    assert!(app.find_command("one").is_none());
+}
+
+#[test]
+fn stale_command_index_falls_back_to_search() {
+    let mut cmd = Command::default("repo")
+        .subcommand(Command::default("add"))
+        .subcommand(Command::default("list"))
+        .flag(Flag::new("all").short("a"))
+        .flag(Flag::new("verbose").short("v"));
+    let Command::Flex(flex_cmd) = &mut cmd else {
+        unreachable!()
+    };
+    assert!(flex_cmd.find_subcommand("list").is_some());
+    assert!(flex_cmd.find_short_flag("v").is_some());
+    flex_cmd.sub_commands.remove(0);
+    flex_cmd.flags.remove(0);
+    assert_eq!(flex_cmd.find_subcommand("list").map(Command::get_cmd_name), Some("list"));
+    assert!(flex_cmd.find_subcommand("add").is_none());
+    assert_eq!(flex_cmd.find_short_flag("v").map(|flag| flag.name.as_str()), Some("verbose"));
+    assert!(flex_cmd.find_flag("all").is_none());
+}
+
+#[test]
+fn index_covers_entries_pushed_after_it_was_built() {
+    let mut cmd = Command::default("repo").subcommand(Command::default("add"));
+    let Command::Flex(flex_cmd) = &mut cmd else {
+        unreachable!()
+    };
+    assert!(flex_cmd.find_subcommand("list").is_none());
+    flex_cmd.sub_commands.push(Command::default("list"));
+    flex_cmd.flags.push(Flag::new("all").short("a"));
+    assert_eq!(flex_cmd.find_subcommand("list").map(Command::get_cmd_name), Some("list"));
+    assert_eq!(flex_cmd.find_short_flag("a").map(|flag| flag.name.as_str()), Some("all"));
+}
@@ -0,0 +1,46 @@
+use flex::{App, Arg, Command, CommandError, Flag, Flex, Matches, Output, State};
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn typed_action_takes_two_type_parameters() {
+    let cmd = Command::default("name").typed_action::<Matches, _>(|m| Ok(m.name.clone()));
+    assert_eq!(cmd.run(&[]).unwrap(), "name");
+}
+
+#[test]
+fn output_action_renders_with_output_flag() {
+    let app = App::new("flex").add_command(
+        Command::default("list")
+            .output_action(|_: &State, _: Matches| Ok(Output::table(["id", "path"], [["1", "a"], ["2", "b"]]))),
+    );
+    assert_eq!(app.run_from(args(&["list", "--output", "csv"])).unwrap(), "id,path\n1,a\n2,b");
+    assert!(app.run_from(args(&["--output=json", "list"])).unwrap().starts_with("["));
+}
+
+#[test]
+fn command_output_flag_is_left_to_the_command() {
+    let app = App::new("flex")
+        .add_command(
+            Command::default("export")
+                .flag(Flag::new("output").map())
+                .typed_action(|m: Matches| Ok(format!("{:?}", m.map("output")))),
+        )
+        .add_command(Command::default("exec").action(|args| Ok::<_, CommandError>(args.join(" "))));
+    assert_eq!(app.run_from(args(&["export", "--output", "path=x"])).unwrap(), r#"[("path", "x")]"#);
+    assert_eq!(app.run_from(args(&["exec", "git", "log", "--output=x"])).unwrap(), "git log --output=x");
+    assert_eq!(app.run_from(args(&["--output", "json", "exec", "a"])).unwrap(), r#""a""#);
+}
+
+#[test]
+fn rest_argument_keeps_its_output_flag() {
+    let app = App::new("flex").add_command(
+        Command::default("exec")
+            .arg(Arg::new("command").rest())
+            .typed_action(|m: Matches| Ok(m.values_of("command").join(" "))),
+    );
+    assert_eq!(app.run_from(args(&["exec", "git", "log", "--output=oneline"])).unwrap(), "git log --output=oneline");
+    assert_eq!(app.run_from(args(&["--output", "json", "exec", "git"])).unwrap(), r#""git""#);
+}
@@ -0,0 +1,25 @@
+#![cfg(unix)]
+
+use std::{fs, os::unix::fs::PermissionsExt};
+
+use flex::{App, AppError};
+
+#[test]
+fn plugin_gets_its_arguments_untouched_and_passes_on_its_exit_code() {
+    let dir = std::env::temp_dir().join(format!("flex-test-plugin-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let seen = dir.join("args");
+    let plugin = dir.join("flex-foo");
+    fs::write(&plugin, format!("#!/bin/sh\nprintf '%s\\n' \"$@\" > '{}'\nexit 3\n", seen.display())).unwrap();
+    fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+    // SAFETY: this is the only test in this binary, so nothing reads PATH concurrently.
+    unsafe { std::env::set_var("PATH", &dir) };
+
+    let app = App::new("flex").plugins().response_files(8);
+    let args = ["foo", "--no-pager", "--output", "json", "--no-input", "@list", "--", "x"];
+    let err = app.run_from(args.iter().map(|arg| arg.to_string()).collect()).unwrap_err();
+
+    assert!(matches!(err.downcast_ref::<AppError>(), Some(AppError::PluginExit(3))), "{}", err);
+    assert_eq!(fs::read_to_string(&seen).unwrap().lines().collect::<Vec<_>>(), &args[1..]);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,77 @@
+use flex::{App, Arg, Command, Flag, Flex, Matches};
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn show(matches: Matches) -> Result<String, Box<dyn std::error::Error>> {
+    let flag = if matches.is_present("verbose") { "-v " } else { "" };
+    Ok(format!("{}{}", flag, matches.values_of("values").join(" ")))
+}
+
+fn values_command() -> Command {
+    Command::default("values")
+        .arg(Arg::new("values").multiple())
+        .flag(Flag::new("verbose").short("v"))
+        .typed_action(show)
+}
+
+#[test]
+fn double_dash_ends_flags() {
+    let cmd = values_command();
+    assert_eq!(cmd.run(&args(&["-v", "a"])).unwrap(), "-v a");
+    assert_eq!(cmd.run(&args(&["--", "-v", "--verbose"])).unwrap(), "-v --verbose");
+    assert_eq!(cmd.run(&args(&["a", "--", "--"])).unwrap(), "a --");
+}
+
+#[test]
+fn negative_number_is_a_value() {
+    let cmd = values_command();
+    assert_eq!(cmd.run(&args(&["-5", "-1.5"])).unwrap(), "-5 -1.5");
+    let cmd = Command::default("top")
+        .arg(Arg::new("values").multiple())
+        .flag(Flag::new("five").short("5"))
+        .typed_action(|m: Matches| Ok(m.is_present("five").to_string()));
+    assert_eq!(cmd.run(&args(&["-5"])).unwrap(), "true");
+}
+
+#[test]
+fn lone_dash_is_stdin() {
+    let cmd = Command::default("cat")
+        .arg(Arg::new("input"))
+        .typed_action(|m: Matches| Ok(format!("{} {}", m.value("input").unwrap_or(""), m.is_stdin("input"))));
+    assert_eq!(cmd.run(&args(&["-"])).unwrap(), "- true");
+    assert_eq!(cmd.run(&args(&["file"])).unwrap(), "file false");
+}
+
+#[test]
+fn exec_passes_command_through_with_raw_action() {
+    let app = App::new("flex").add_command(
+        Command::default("exec").action(|args| Ok::<_, flex::CommandError>(args.join(" "))),
+    );
+    assert_eq!(app.run_from(args(&["exec", "--", "git", "log", "-n", "5"])).unwrap(), "-- git log -n 5");
+}
+
+#[test]
+fn exec_passes_command_through_with_typed_action() {
+    let app = App::new("flex").add_command(
+        Command::default("exec")
+            .arg(Arg::new("command").rest().required())
+            .flag(Flag::new("verbose").short("v"))
+            .typed_action(|m: Matches| Ok(m.values_of("command").join(" "))),
+    );
+    assert_eq!(app.run_from(args(&["exec", "--", "git", "log", "-n", "5"])).unwrap(), "git log -n 5");
+    assert_eq!(app.run_from(args(&["exec", "git", "log", "-v", "5"])).unwrap(), "git log -v 5");
+}
+
+#[test]
+fn app_switches_only_before_the_command_name() {
+    let app = App::new("flex").add_command(
+        Command::default("exec").action(|args| Ok::<_, flex::CommandError>(args.join(" "))),
+    );
+    assert_eq!(app.run_from(args(&["--no-pager", "--no-input", "exec", "a"])).unwrap(), "a");
+    assert_eq!(
+        app.run_from(args(&["exec", "git", "--no-pager", "--no-input", "log"])).unwrap(),
+        "git --no-pager --no-input log"
+    );
+}
@@ -0,0 +1,75 @@
+use std::{fs, path::PathBuf};
+
+use flex::{App, Command, CommandError, Flex};
+
+fn app(max_depth: usize) -> App {
+    App::new("flex")
+        .response_files(max_depth)
+        .add_command(Command::default("echo").action(|args| Ok::<_, CommandError>(format!("{:?}", args))))
+}
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("flex-test-response-{}-{}", name, std::process::id()));
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    dir
+}
+
+fn run(app: &App, args: &[&str]) -> Result<String, String> {
+    app.run_from(args.iter().map(|arg| arg.to_string()).collect()).map_err(|e| e.to_string())
+}
+
+#[test]
+fn quoting_and_comments() {
+    let dir = dir("quoting");
+    let file = dir.join("args");
+    fs::write(&file, "# a comment\n  # another\n'a b' \"c \\\"d\\\"\" e\\ f\ng # not a comment\n").unwrap();
+    let at = format!("@{}", file.display());
+    assert_eq!(
+        run(&app(8), &["echo", &at]).unwrap(),
+        r##"["a b", "c \"d\"", "e f", "g", "#", "not", "a", "comment"]"##
+    );
+    fs::write(&file, "'open").unwrap();
+    let err = run(&app(8), &["echo", &at]).unwrap_err();
+    assert!(err.ends_with(":1: unterminated single quote"), "{}", err);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn escapes_and_double_dash() {
+    let dir = dir("escapes");
+    let file = dir.join("args");
+    fs::write(&file, "x").unwrap();
+    let at = format!("@{}", file.display());
+    assert_eq!(run(&app(8), &["echo", "@@name", &at, "--", &at]).unwrap(), format!("[\"@name\", \"x\", \"--\", {:?}]", at));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn nested_paths_are_relative_to_their_file() {
+    let dir = dir("nested");
+    fs::write(dir.join("outer"), "a @sub/inner").unwrap();
+    fs::write(dir.join("sub").join("inner"), "b @deepest").unwrap();
+    fs::write(dir.join("sub").join("deepest"), "c").unwrap();
+    let at = format!("@{}", dir.join("outer").display());
+    assert_eq!(run(&app(2), &["echo", &at]).unwrap(), r#"["a", "b", "c"]"#);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn depth_limit_counts_nested_files() {
+    let dir = dir("depth");
+    fs::write(dir.join("outer"), "a @inner").unwrap();
+    fs::write(dir.join("inner"), "b").unwrap();
+    fs::write(dir.join("self"), "@self").unwrap();
+    let outer = format!("@{}", dir.join("outer").display());
+    let inner = format!("@{}", dir.join("inner").display());
+
+    assert_eq!(run(&app(0), &["echo", &inner]).unwrap(), r#"["b"]"#);
+    let err = run(&app(0), &["echo", &outer]).unwrap_err();
+    assert!(err.contains("is nested more than 0 files deep"), "{}", err);
+    assert_eq!(run(&app(1), &["echo", &outer]).unwrap(), r#"["a", "b"]"#);
+
+    let err = run(&app(8), &["echo", &format!("@{}", dir.join("self").display())]).unwrap_err();
+    assert!(err.contains("is nested more than 8 files deep (is a file including itself?)"), "{}", err);
+    fs::remove_dir_all(dir).unwrap();
+}
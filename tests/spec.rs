@@ -0,0 +1,83 @@
+#![cfg(feature = "spec")]
+
+use flex::{App, SpecFormat};
+
+fn spec_error(source: &str, format: SpecFormat) -> String {
+    App::new("flex").spec(source, format).unwrap_err().to_string()
+}
+
+#[test]
+fn bad_nested_key_is_named_in_toml() {
+    let source = r#"
+        [[commands]]
+        name = "repo"
+
+        [[commands.flags]]
+        name = "all"
+
+        [[commands.flags]]
+        name = "verbose"
+        short = "vv"
+    "#;
+    assert_eq!(
+        spec_error(source, SpecFormat::Toml),
+        "Invalid configuration: spec key `commands[0].flags[1].short`: expected a single character"
+    );
+}
+
+#[test]
+fn bad_nested_key_is_named_in_json() {
+    let source = r#"{"commands": [{"name": "repo", "flags": [{"name": "all"}, {"name": "verbose", "short": "vv"}]}]}"#;
+    assert_eq!(
+        spec_error(source, SpecFormat::Json),
+        "Invalid configuration: spec key `commands[0].flags[1].short`: expected a single character"
+    );
+}
+
+#[test]
+fn unregistered_action_is_named_in_toml() {
+    let source = r#"
+        [[commands]]
+        name = "repo"
+
+        [[commands.subcommands]]
+        name = "list"
+        action = "list-repos"
+    "#;
+    assert_eq!(
+        spec_error(source, SpecFormat::Toml),
+        "Invalid configuration: spec key `commands[0].subcommands[0].action`: no action named 'list-repos' is registered"
+    );
+}
+
+#[test]
+fn unregistered_action_is_named_in_json() {
+    let source = r#"{"commands": [{"name": "repo", "subcommands": [{"name": "list", "action": "list-repos"}]}]}"#;
+    assert_eq!(
+        spec_error(source, SpecFormat::Json),
+        "Invalid configuration: spec key `commands[0].subcommands[0].action`: no action named 'list-repos' is registered"
+    );
+}
+
+#[test]
+fn unknown_and_mistyped_keys_are_named() {
+    let source = r#"{"commands": [{"name": "repo", "args": [{"name": "path", "requird": true}]}]}"#;
+    assert_eq!(
+        spec_error(source, SpecFormat::Json),
+        "Invalid configuration: spec key `commands[0].args[0].requird`: unknown key"
+    );
+    let source = "[[commands]]\nname = \"repo\"\naliases = [\"r\", 1]\n";
+    assert_eq!(
+        spec_error(source, SpecFormat::Toml),
+        "Invalid configuration: spec key `commands[0].aliases[1]`: expected a string"
+    );
+}
+
+#[test]
+fn registered_action_runs() {
+    let app = App::new("flex")
+        .register_action("hello", |args| Ok::<_, flex::CommandError>(format!("hello {}", args.join(" "))))
+        .spec(r#"{"commands": [{"name": "greet", "action": "hello"}]}"#, SpecFormat::Json)
+        .unwrap();
+    assert_eq!(app.run_from(vec!["greet".into(), "you".into()]).unwrap(), "hello you");
+}
@@ -0,0 +1,58 @@
+use flex::{App, Arg, Command, Flag, Flex};
+
+fn problems(app: App) -> Vec<String> {
+    match app.validate() {
+        Ok(()) => Vec::new(),
+        Err(e) => e.to_string().trim_start_matches("Invalid configuration: ").lines().map(String::from).collect(),
+    }
+}
+
+#[test]
+fn duplicate_help_is_reported() {
+    let app = App::new("flex").add_command(Command::default("repo").help().help());
+    assert_eq!(problems(app), ["flex repo: duplicate subcommand 'help'"]);
+}
+
+#[test]
+fn clashing_short_flag_is_reported() {
+    let repo = Command::default("repo").subcommand(
+        Command::default("add")
+            .flag(Flag::new("all").short("a"))
+            .flag(Flag::new("author").short("a")),
+    );
+    let app = App::new("flex").add_command(repo);
+    assert_eq!(problems(app), ["flex repo add: flags '--all' and '--author' share short flag '-a'"]);
+}
+
+#[test]
+fn required_after_optional_is_reported() {
+    let cmd = Command::default("add")
+        .arg(Arg::new("name"))
+        .arg(Arg::new("path").required());
+    let app = App::new("flex").add_command(Command::default("repo").subcommand(cmd));
+    assert_eq!(
+        problems(app),
+        ["flex repo add: required argument <path> is declared after optional argument <name>"]
+    );
+}
+
+#[test]
+fn multiple_values_before_last_is_reported() {
+    let cmd = Command::default("add")
+        .arg(Arg::new("paths").multiple())
+        .arg(Arg::new("name"));
+    let app = App::new("flex").add_command(Command::default("repo").subcommand(cmd));
+    assert_eq!(
+        problems(app),
+        ["flex repo add: argument <paths> takes multiple values but is not the last argument"]
+    );
+}
+
+#[test]
+fn missing_default_subcommand_is_reported() {
+    let repo = Command::default("repo")
+        .subcommand(Command::default("list"))
+        .default_subcommand("lsit");
+    let app = App::new("flex").add_command(repo);
+    assert_eq!(problems(app), ["flex repo: default subcommand 'lsit' does not exist"]);
+}